@@ -0,0 +1,283 @@
+use crate::tabs;
+use crate::types;
+
+// every command, in the order they're suggested
+pub const COMMANDS: [&str; 60] = [
+    "add_entity",
+    "add_event",
+    "remove_entity",
+    "add_effect",
+    "remove_effect",
+    "damage",
+    "heal",
+    "rest",
+    "hit_dice",
+    "exhaustion",
+    "resource",
+    "use",
+    "restore",
+    "hide",
+    "reveal",
+    "max_hp",
+    "initiative",
+    "next",
+    "move_turn",
+    "delay",
+    "spend",
+    "regain",
+    "faction",
+    "factions",
+    "serve",
+    "stop_server",
+    "api",
+    "stop_api",
+    "export_log",
+    "save_log",
+    "replay",
+    "replay_step",
+    "end_replay",
+    "stats",
+    "cr",
+    "xp",
+    "level",
+    "rules",
+    "difficulty",
+    "end_combat",
+    "encounters",
+    "start",
+    "strict",
+    "templates",
+    "note",
+    "tag",
+    "untag",
+    "campaign",
+    "tabs",
+    "new_tab",
+    "tab",
+    "rename_tab",
+    "close_tab",
+    "move_to_tab",
+    "save_tabs",
+    "load_tabs",
+    "clear",
+    "save",
+    "load",
+    "help",
+];
+
+const SELECTORS: [&str; 8] = [
+    "@all",
+    "@turn",
+    "@bloodied",
+    "@down",
+    "@hidden",
+    "@has:",
+    "@tag:",
+    "@note:",
+];
+
+// candidates for the word under the cursor, best match first,
+// word_start is where in the line the completed word goes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Completion {
+    pub candidates: Vec<String>,
+    pub word_start: usize,
+}
+
+impl Completion {
+    // the line with the word under the cursor replaced by a candidate
+    pub fn apply(&self, line: &str, candidate: usize) -> String {
+        line[..self.word_start].to_string() + &self.candidates[candidate]
+    }
+}
+
+// prefix matches come first, then the word anywhere in the candidate,
+// then the letters of the word in order
+fn fuzzy_score(word: &str, candidate: &str) -> Option<u8> {
+    let word = word.to_lowercase();
+    let candidate = candidate.to_lowercase();
+    if candidate.starts_with(&word) {
+        return Some(0);
+    }
+    if candidate.contains(&word) {
+        return Some(1);
+    }
+    let mut letters = candidate.chars();
+    if word.chars().all(|c| letters.any(|x| x == c)) {
+        return Some(2);
+    }
+    None
+}
+
+fn targets(game: &mut types::Game) -> Vec<String> {
+    let mut words = Vec::from_iter(
+        game.get_entities()
+            .iter()
+            .filter(|x| x.event().is_none())
+            .map(|x| x.name().to_string()),
+    );
+    words.extend(SELECTORS.iter().map(|x| x.to_string()));
+    for faction in game.get_faction_ids() {
+        words.push("@".to_string() + &faction);
+    }
+    for effect in game.get_effect_names() {
+        words.push("@has:".to_string() + &effect);
+    }
+    for tag in game.get_tag_names() {
+        words.push("@tag:".to_string() + &tag);
+    }
+    words
+}
+
+fn words(list: &[&str]) -> Vec<String> {
+    list.iter().map(|x| x.to_string()).collect()
+}
+
+// what makes sense at this position of the command, position 1 being the first argument
+fn context_words(tabs: &mut tabs::Tabs, command: &str, position: usize) -> Vec<String> {
+    let tab_names = tabs.get_names();
+    let game = tabs.game();
+    match (command, position) {
+        (_, 0) => words(&COMMANDS),
+        ("help", _) => {
+            let mut words = words(&COMMANDS);
+            words.push("selectors".to_string());
+            words
+        }
+        ("add_entity", 1) | ("add_entity", 3..) => game.get_template_names(),
+        ("add_entity", 2) | ("save", 1) | ("faction", 1) => {
+            let mut words = game.get_faction_ids();
+            if command == "save" {
+                words.push("all".to_string());
+            }
+            words
+        }
+        ("add_effect", 1) | ("remove_effect", 1) => game.get_effect_names(),
+        ("add_effect", 3) => words(&["turns", "minutes", "hours"]),
+        ("add_effect", 2) | ("damage", 1) | ("heal", 1) | ("max_hp", 1) => Vec::new(),
+        ("rest", 1) => words(&["short", "long"]),
+        ("resource", 3) => words(&["turn", "round", "short", "long"]),
+        ("resource", 1..=2) => Vec::new(),
+        ("use", 1) | ("restore", 1) => game.get_resource_names(),
+        ("spend", 1) | ("regain", 1) => words(&["action", "bonus", "reaction", "movement"]),
+        ("initiative", 1) => words(&["roll"]),
+        ("strict", 1) => words(&["on", "off"]),
+        ("rules", 1) => words(&["5e", "pf2e"]),
+        ("cr", 1) | ("xp", 1) | ("level", 1) => Vec::new(),
+        ("replay_step", 1) => words(&["next", "prev"]),
+        ("load", 1) => game.get_save_names(),
+        ("start", 1) => game.get_encounter_names(),
+        ("campaign", 1) => game.get_campaign_names(),
+        ("tab", 1) | ("close_tab", 1) | ("move_to_tab", 1) => tab_names,
+        ("load_tabs", 1) => game.get_tab_save_names(),
+        ("tag", 2..) | ("untag", 2..) => game.get_tag_names(),
+        ("note", 2..) => Vec::new(),
+        ("faction", _)
+        | ("serve", _)
+        | ("api", _)
+        | ("export_log", _)
+        | ("save_log", _)
+        | ("replay", _)
+        | ("save", _)
+        | ("load", _)
+        | ("start", _)
+        | ("campaign", _)
+        | ("new_tab", _)
+        | ("tab", _)
+        | ("rename_tab", _)
+        | ("close_tab", _)
+        | ("save_tabs", _)
+        | ("load_tabs", _) => Vec::new(),
+        _ => targets(game),
+    }
+}
+
+// completes the last word of a command line, kept apart from the ui so any front end can use it
+pub fn complete(tabs: &mut tabs::Tabs, line: &str) -> Completion {
+    let word_start = line.rfind(' ').map(|x| x + 1).unwrap_or(0);
+    let word = &line[word_start..];
+    // options don't count towards the position, the same as when running the command
+    let before = Vec::from_iter(
+        line[..word_start]
+            .split_whitespace()
+            .filter(|x| !x.starts_with("--")),
+    );
+    let command = before
+        .first()
+        .map(|x| types::argumment_abreviations(x))
+        .unwrap_or("");
+    let mut candidates = if word.starts_with("--") {
+        words(types::command_flags(command))
+    } else {
+        context_words(tabs, command, before.len())
+    };
+    let game = tabs.game();
+    // effects can only be removed from whoever has them
+    if command == "remove_effect" && before.len() >= 2 {
+        let effect = before[1];
+        candidates = Vec::from_iter(
+            game.get_entities()
+                .iter()
+                .filter(|x| {
+                    x.status_effects()
+                        .iter()
+                        .any(|x| x.name().eq_ignore_ascii_case(effect))
+                })
+                .map(|x| x.name().to_string()),
+        );
+    }
+    // the source of damage and healing comes after by
+    if matches!(command, "damage" | "heal") && before.last() == Some(&"by") {
+        candidates = targets(game);
+        candidates.retain(|x| !x.starts_with('@'));
+    }
+    let mut scored: Vec<(u8, String)> = Vec::new();
+    for candidate in candidates {
+        if scored.iter().any(|(_, x)| x == &candidate) {
+            continue;
+        }
+        if let Some(score) = fuzzy_score(word, &candidate) {
+            scored.push((score, candidate));
+        }
+    }
+    scored.sort_by_key(|(score, _)| *score);
+    Completion {
+        candidates: scored.into_iter().map(|(_, x)| x).collect(),
+        word_start,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(tabs: &mut tabs::Tabs, line: &str) -> Vec<String> {
+        complete(tabs, line).candidates
+    }
+
+    #[test]
+    fn prefix_matches_come_first() {
+        assert_eq!(fuzzy_score("dam", "damage"), Some(0));
+        assert_eq!(fuzzy_score("age", "damage"), Some(1));
+        assert_eq!(fuzzy_score("dmg", "damage"), Some(2));
+        assert_eq!(fuzzy_score("xyz", "damage"), None);
+        let mut tabs = tabs::Tabs::default();
+        assert_eq!(
+            candidates(&mut tabs, "re")[..2],
+            ["remove_entity", "remove_effect"]
+        );
+    }
+
+    #[test]
+    fn completes_from_the_context() {
+        let mut tabs = tabs::Tabs::default();
+        tabs.process_command("add_entity Goblin enemy".to_string())
+            .unwrap();
+        assert_eq!(candidates(&mut tabs, "damage 5 gob"), ["Goblin"]);
+        assert_eq!(candidates(&mut tabs, "damage 5 @hid"), ["@hidden"]);
+        assert_eq!(candidates(&mut tabs, "damage 5 Goblin --c"), ["--crit"]);
+        assert_eq!(candidates(&mut tabs, "rest sh"), ["short"]);
+        let completion = complete(&mut tabs, "d 5 gob");
+        assert_eq!(completion.apply("d 5 gob", 0), "d 5 Goblin");
+    }
+}
@@ -0,0 +1,167 @@
+// small self contained dice roller so rolls don't need an extra dependency
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0x2545_f491_4f6c_dd1d);
+        Rng::new(seed)
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // xorshift gets stuck on zero
+        Rng {
+            state: if seed == 0 {
+                0x2545_f491_4f6c_dd1d
+            } else {
+                seed
+            },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    // rolls a single die, 1..=sides
+    pub fn roll_die(&mut self, sides: u16) -> u16 {
+        if sides == 0 {
+            return 0;
+        }
+        (self.next_u64() % sides as u64) as u16 + 1
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct DiceExpression {
+    pub count: u16,
+    pub sides: u16,
+    pub bonus: i16,
+}
+
+impl DiceExpression {
+    // parses expressions like "d8", "3d6", "2d10+4" and "1d12-1"
+    pub fn parse(expression: &str) -> Result<DiceExpression, String> {
+        let expression = expression.to_lowercase();
+        let (dice, bonus) = match expression.find(['+', '-']) {
+            Some(index) => (
+                &expression[..index],
+                expression[index..]
+                    .trim_start_matches('+')
+                    .parse::<i16>()
+                    .map_err(|_| "Invalid dice bonus".to_string())?,
+            ),
+            None => (expression.as_str(), 0),
+        };
+        let parts = dice.split('d').collect::<Vec<&str>>();
+        if parts.len() != 2 {
+            return Err("Invalid dice expression, expected something like 2d6+3".to_string());
+        }
+        let count = if parts[0].is_empty() {
+            1
+        } else {
+            parts[0]
+                .parse::<u16>()
+                .map_err(|_| "Invalid dice count".to_string())?
+        };
+        let sides = parts[1]
+            .parse::<u16>()
+            .map_err(|_| "Invalid die size".to_string())?;
+        Ok(DiceExpression {
+            count,
+            sides,
+            bonus,
+        })
+    }
+
+    pub fn roll(&self, rng: &mut Rng) -> u16 {
+        let mut total: i32 = self.bonus as i32;
+        for _ in 0..self.count {
+            total += rng.roll_die(self.sides) as i32;
+        }
+        total.max(0) as u16
+    }
+}
+
+impl std::fmt::Display for DiceExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}d{}", self.count, self.sides)?;
+        if self.bonus > 0 {
+            write!(f, "+{}", self.bonus)?;
+        } else if self.bonus < 0 {
+            write!(f, "{}", self.bonus)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_expressions() {
+        assert_eq!(
+            DiceExpression::parse("d8"),
+            Ok(DiceExpression {
+                count: 1,
+                sides: 8,
+                bonus: 0
+            })
+        );
+        assert_eq!(
+            DiceExpression::parse("2D10+4"),
+            Ok(DiceExpression {
+                count: 2,
+                sides: 10,
+                bonus: 4
+            })
+        );
+        assert_eq!(
+            DiceExpression::parse("1d12-1"),
+            Ok(DiceExpression {
+                count: 1,
+                sides: 12,
+                bonus: -1
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expression in ["", "8", "2d", "xd6", "d6+x", "2d6d8"] {
+            assert!(DiceExpression::parse(expression).is_err(), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn displays_like_it_parses() {
+        for expression in ["1d8", "3d6+2", "1d12-1"] {
+            let parsed = DiceExpression::parse(expression).unwrap();
+            assert_eq!(parsed.to_string(), expression);
+        }
+    }
+
+    #[test]
+    fn rolls_stay_in_range() {
+        let mut rng = Rng::new(1);
+        let expression = DiceExpression::parse("2d6+1").unwrap();
+        for _ in 0..1000 {
+            let roll = expression.roll(&mut rng);
+            assert!((3..=13).contains(&roll));
+        }
+        assert_eq!(DiceExpression::parse("1d4-10").unwrap().roll(&mut rng), 0);
+    }
+}
@@ -0,0 +1,230 @@
+// which game's encounter building rules are used for difficulty and xp
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+pub enum RuleSet {
+    #[default]
+    Dnd5e,
+    Pathfinder2e,
+}
+
+impl std::fmt::Display for RuleSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RuleSet::Dnd5e => write!(f, "5e"),
+            RuleSet::Pathfinder2e => write!(f, "PF2e"),
+        }
+    }
+}
+
+impl TryFrom<String> for RuleSet {
+    type Error = String;
+
+    fn try_from(rules: String) -> Result<Self, Self::Error> {
+        match rules.to_lowercase().as_str() {
+            "5e" | "dnd" | "dnd5e" => Ok(RuleSet::Dnd5e),
+            "pf2e" | "pf2" | "pathfinder" => Ok(RuleSet::Pathfinder2e),
+            _ => Err("Expected 5e or pf2e".to_string()),
+        }
+    }
+}
+
+// xp per character for easy, medium, hard and deadly encounters at levels 1 to 20
+const DND5E_THRESHOLDS: [[u32; 4]; 20] = [
+    [25, 50, 75, 100],
+    [50, 100, 150, 200],
+    [75, 150, 225, 400],
+    [125, 250, 375, 500],
+    [250, 500, 750, 1100],
+    [300, 600, 900, 1400],
+    [350, 750, 1100, 1700],
+    [450, 900, 1400, 2100],
+    [550, 1100, 1600, 2400],
+    [600, 1200, 1900, 2800],
+    [800, 1600, 2400, 3600],
+    [1000, 2000, 3000, 4500],
+    [1100, 2200, 3400, 5100],
+    [1250, 2500, 3800, 5700],
+    [1400, 2800, 4300, 6400],
+    [1600, 3200, 4800, 7200],
+    [2000, 3900, 5900, 8800],
+    [2100, 4200, 6300, 9500],
+    [2400, 4900, 7300, 10900],
+    [2800, 5700, 8500, 12700],
+];
+
+// xp for challenge ratings 1 to 30, the fractions are handled on their own
+const DND5E_CR_XP: [u32; 30] = [
+    200, 450, 700, 1100, 1800, 2300, 2900, 3900, 5000, 5900, 7200, 8400, 10000, 11500, 13000,
+    15000, 18000, 20000, 22000, 25000, 33000, 41000, 50000, 62000, 75000, 90000, 105000, 120000,
+    135000, 155000,
+];
+
+// accepts "1/4" as well as 0.25
+pub fn cr_to_xp(challenge_rating: &str) -> Option<u32> {
+    match challenge_rating.trim() {
+        "0" => Some(10),
+        "1/8" | "0.125" => Some(25),
+        "1/4" | "0.25" => Some(50),
+        "1/2" | "0.5" => Some(100),
+        cr => {
+            let cr = cr.parse::<f64>().ok()?;
+            if cr.fract() != 0.0 || !(1.0..=30.0).contains(&cr) {
+                return None;
+            }
+            Some(DND5E_CR_XP[cr as usize - 1])
+        }
+    }
+}
+
+// more monsters make an encounter harder than their xp alone, adjusted for party size
+fn dnd5e_multiplier(monsters: usize, party_size: usize) -> f64 {
+    const MULTIPLIERS: [f64; 7] = [0.5, 1.0, 1.5, 2.0, 2.5, 3.0, 4.0];
+    let index: usize = match monsters {
+        0..=1 => 1,
+        2 => 2,
+        3..=6 => 3,
+        7..=10 => 4,
+        11..=14 => 5,
+        _ => 6,
+    };
+    let index = if party_size < 3 {
+        (index + 1).min(6)
+    } else if party_size >= 6 {
+        index - 1
+    } else {
+        index
+    };
+    MULTIPLIERS[index]
+}
+
+// difficulty from the party's levels and the xp of each monster
+pub fn dnd5e(party_levels: &[i16], monster_xp: &[u32]) -> String {
+    let mut thresholds = [0; 4];
+    for level in party_levels {
+        let row = DND5E_THRESHOLDS[(*level).clamp(1, 20) as usize - 1];
+        for (threshold, xp) in thresholds.iter_mut().zip(row) {
+            *threshold += xp;
+        }
+    }
+    let total: u32 = monster_xp.iter().sum();
+    let adjusted = (total as f64 * dnd5e_multiplier(monster_xp.len(), party_levels.len())) as u32;
+    let difficulty = ["Easy", "Medium", "Hard", "Deadly"]
+        .iter()
+        .zip(thresholds)
+        .rev()
+        .find(|(_, threshold)| adjusted >= *threshold)
+        .map(|(difficulty, _)| *difficulty)
+        .unwrap_or("Trivial");
+    format!(
+        "{} ({} adjusted XP, thresholds {}/{}/{}/{})",
+        difficulty, adjusted, thresholds[0], thresholds[1], thresholds[2], thresholds[3]
+    )
+}
+
+// xp a creature is worth to a pf2e party, by how far its level is from the party's
+pub fn pf2e_creature_xp(creature_level: i16, party_level: i16) -> u32 {
+    match creature_level - party_level {
+        ..=-5 => 0,
+        -4 => 10,
+        -3 => 15,
+        -2 => 20,
+        -1 => 30,
+        0 => 40,
+        1 => 60,
+        2 => 80,
+        3 => 120,
+        _ => 160,
+    }
+}
+
+// the party level is the average, budgets are for four characters and move per extra one
+pub fn pf2e(party_levels: &[i16], creature_levels: &[i16]) -> String {
+    let party_level = pf2e_party_level(party_levels);
+    let total: u32 = creature_levels
+        .iter()
+        .map(|x| pf2e_creature_xp(*x, party_level))
+        .sum();
+    let extra = party_levels.len() as i64 - 4;
+    let budgets = Vec::from_iter(
+        [(40, 10), (60, 15), (80, 20), (120, 30), (160, 40)]
+            .iter()
+            .map(|(budget, per_character)| (budget + extra * per_character).max(0) as u32),
+    );
+    let difficulty = ["Trivial", "Low", "Moderate", "Severe", "Extreme"]
+        .iter()
+        .zip(budgets.iter())
+        .rev()
+        .find(|(_, budget)| total >= **budget)
+        .map(|(difficulty, _)| *difficulty)
+        .unwrap_or("Trivial");
+    format!(
+        "{} ({} XP at party level {}, budgets {}/{}/{}/{}/{})",
+        difficulty, total, party_level, budgets[0], budgets[1], budgets[2], budgets[3], budgets[4]
+    )
+}
+
+pub fn pf2e_party_level(party_levels: &[i16]) -> i16 {
+    if party_levels.is_empty() {
+        return 1;
+    }
+    let total: i32 = party_levels.iter().map(|x| *x as i32).sum();
+    (total as f64 / party_levels.len() as f64).round() as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dnd5e_thresholds_add_up_per_character() {
+        assert_eq!(
+            dnd5e(&[1, 1, 1, 1], &[200]),
+            "Medium (200 adjusted XP, thresholds 100/200/300/400)"
+        );
+        assert_eq!(
+            dnd5e(&[5, 5, 5, 5], &[1100, 1100]),
+            "Hard (3300 adjusted XP, thresholds 1000/2000/3000/4400)"
+        );
+        assert!(dnd5e(&[20], &[10]).starts_with("Trivial"));
+    }
+
+    #[test]
+    fn dnd5e_multiplier_follows_monsters_and_party_size() {
+        assert_eq!(dnd5e_multiplier(1, 4), 1.0);
+        assert_eq!(dnd5e_multiplier(4, 4), 2.0);
+        assert_eq!(dnd5e_multiplier(4, 2), 2.5);
+        assert_eq!(dnd5e_multiplier(1, 6), 0.5);
+    }
+
+    #[test]
+    fn challenge_ratings_convert_to_xp() {
+        assert_eq!(cr_to_xp("1/4"), Some(50));
+        assert_eq!(cr_to_xp("0.5"), Some(100));
+        assert_eq!(cr_to_xp("30"), Some(155000));
+        assert_eq!(cr_to_xp("31"), None);
+        assert_eq!(cr_to_xp("1.5"), None);
+    }
+
+    #[test]
+    fn pf2e_budgets_move_per_extra_character() {
+        assert_eq!(
+            pf2e(&[1, 1, 1, 1], &[1, 1]),
+            "Moderate (80 XP at party level 1, budgets 40/60/80/120/160)"
+        );
+        assert_eq!(
+            pf2e(&[1, 1, 1, 1, 1], &[1, 1]),
+            "Low (80 XP at party level 1, budgets 50/75/100/150/200)"
+        );
+        assert_eq!(
+            pf2e(&[3, 3, 3], &[]),
+            "Trivial (0 XP at party level 3, budgets 30/45/60/90/120)"
+        );
+    }
+
+    #[test]
+    fn pf2e_creature_xp_follows_level_difference() {
+        assert_eq!(pf2e_creature_xp(-4, 1), 0);
+        assert_eq!(pf2e_creature_xp(1, 3), 20);
+        assert_eq!(pf2e_creature_xp(5, 1), 160);
+        assert_eq!(pf2e_party_level(&[1, 2, 2]), 2);
+    }
+}
@@ -0,0 +1,226 @@
+// prepared encounters, one file each in here named after the encounter
+pub const ENCOUNTER_DIRECTORY: &str = "encounters";
+
+// a creature line, count above one spawns numbered copies
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncounterMonster {
+    pub name: String,
+    pub count: usize,
+    pub faction: String,
+    // stat block to use, the name is tried when there's none
+    pub template: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncounterEffect {
+    pub targets: String,
+    pub effect: String,
+    pub length: String,
+    pub unit: String,
+}
+
+// the file format follows the save files, one | separated line each:
+// monster|<name>|<count>[|<faction>[|<stat block>]]
+// event|<name>|<initiative>|<reminder>
+// effect|<targets>|<effect>|<length>|<unit>
+// note|<text>
+// map|<reference>
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Encounter {
+    pub name: String,
+    pub monsters: Vec<EncounterMonster>,
+    // name, initiative and reminder
+    pub events: Vec<(String, i16, String)>,
+    pub effects: Vec<EncounterEffect>,
+    pub notes: Vec<String>,
+    pub map: Option<String>,
+}
+
+impl Encounter {
+    // the commands that put the encounter into a game
+    pub fn commands(&self) -> Vec<String> {
+        let mut commands = Vec::new();
+        for monster in self.monsters.iter() {
+            let mut command = "add_entity ".to_string() + &monster.name + " " + &monster.faction;
+            if monster.count > 1 {
+                command.push_str(" x");
+                command.push_str(&monster.count.to_string());
+            }
+            if let Some(template) = &monster.template {
+                command.push(' ');
+                command.push_str(template);
+            }
+            commands.push(command);
+        }
+        for (name, initiative, reminder) in self.events.iter() {
+            commands.push(
+                "add_event ".to_string() + name + " " + &initiative.to_string() + " " + reminder,
+            );
+        }
+        for effect in self.effects.iter() {
+            commands.push(
+                "add_effect ".to_string()
+                    + &effect.effect
+                    + " "
+                    + &effect.length
+                    + " "
+                    + &effect.unit
+                    + " "
+                    + &effect.targets,
+            );
+        }
+        commands
+    }
+
+    // one line overview for the encounters list
+    pub fn summary(&self) -> String {
+        let mut summary = self.name.clone() + ": ";
+        let monsters = Vec::from_iter(
+            self.monsters
+                .iter()
+                .map(|x| x.count.to_string() + " " + &x.name),
+        );
+        summary.push_str(&monsters.join(", "));
+        if let Some(map) = &self.map {
+            summary.push_str(" (map: ");
+            summary.push_str(map);
+            summary.push(')');
+        }
+        summary
+    }
+}
+
+pub fn parse_encounter(name: &str, contents: &str) -> Result<Encounter, String> {
+    let invalid = |line: &str| "Invalid line in encounter ".to_string() + name + ": " + line;
+    // these end up as single words of a command, targets and reminders can be several
+    let words = |line: &str, fields: &[&str]| {
+        if fields
+            .iter()
+            .any(|x| x.is_empty() || x.contains(char::is_whitespace))
+        {
+            return Err(invalid(line));
+        }
+        Ok(())
+    };
+    let mut encounter = Encounter {
+        name: name.to_string(),
+        ..Encounter::default()
+    };
+    for line in contents.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = line.split('|').map(|x| x.trim()).collect::<Vec<&str>>();
+        match (fields[0], fields.len()) {
+            ("monster", 3..=5) => {
+                words(line, &fields[1..])?;
+                let count = fields[2].parse().map_err(|_| invalid(line))?;
+                if count == 0 {
+                    return Err(invalid(line));
+                }
+                encounter.monsters.push(EncounterMonster {
+                    name: fields[1].to_string(),
+                    count,
+                    faction: fields.get(3).unwrap_or(&"enemy").to_string(),
+                    template: fields.get(4).map(|x| x.to_string()),
+                })
+            }
+            ("event", 4) => {
+                words(line, &fields[1..2])?;
+                encounter.events.push((
+                    fields[1].to_string(),
+                    fields[2].parse().map_err(|_| invalid(line))?,
+                    fields[3].to_string(),
+                ))
+            }
+            ("effect", 5) => {
+                words(line, &fields[2..])?;
+                encounter.effects.push(EncounterEffect {
+                    targets: fields[1].to_string(),
+                    effect: fields[2].to_string(),
+                    length: fields[3].to_string(),
+                    unit: fields[4].to_string(),
+                })
+            }
+            // notes are kept as written, | and spaces included
+            ("note", 2..) => encounter
+                .notes
+                .push(line.split_once('|').map(|x| x.1).unwrap_or("").to_string()),
+            ("map", 2) => encounter.map = Some(fields[1].to_string()),
+            _ => return Err(invalid(line)),
+        }
+    }
+    Ok(encounter)
+}
+
+pub fn path(name: &str) -> String {
+    ENCOUNTER_DIRECTORY.to_string() + "/" + name + ".txt"
+}
+
+pub fn load_encounter(name: &str) -> Result<Encounter, String> {
+    match std::fs::read_to_string(path(name)) {
+        Ok(contents) => parse_encounter(name, &contents),
+        Err(_) => Err("No encounter named ".to_string() + name),
+    }
+}
+
+// every encounter in the library, sorted by name
+pub fn list_encounters() -> Vec<String> {
+    let Ok(files) = std::fs::read_dir(ENCOUNTER_DIRECTORY) else {
+        return Vec::new();
+    };
+    let mut names = Vec::from_iter(files.filter_map(|x| x.ok()).filter_map(|x| {
+        x.file_name()
+            .to_str()
+            .and_then(|x| x.strip_suffix(".txt"))
+            .map(|x| x.to_string())
+    }));
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_line_kind() {
+        let encounter = parse_encounter(
+            "ambush",
+            "monster|Goblin|3\n\
+             monster|Boss|1|enemy|Bugbear\n\
+             event|Collapse|20|the ceiling gives way\n\
+             effect|Goblin_1 Goblin_2|Hidden|1|minutes\n\
+             note|they want the cart | not a fight\n\
+             map|cave.png\n",
+        )
+        .unwrap();
+        assert_eq!(encounter.monsters.len(), 2);
+        assert_eq!(encounter.monsters[1].template.as_deref(), Some("Bugbear"));
+        assert_eq!(encounter.notes, ["they want the cart | not a fight"]);
+        assert_eq!(
+            encounter.commands(),
+            [
+                "add_entity Goblin enemy x3",
+                "add_entity Boss enemy Bugbear",
+                "add_event Collapse 20 the ceiling gives way",
+                "add_effect Hidden 1 minutes Goblin_1 Goblin_2",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_fields_that_would_split_a_command() {
+        for contents in [
+            "monster|Goblin Boss|1",
+            "monster|Goblin|1|enemy side",
+            "monster|Goblin|many",
+            "monster|Goblin|0",
+            "event|Lair Action|20|roll",
+            "effect|Goblin|Very Prone|1|turns",
+            "bogus|line",
+        ] {
+            assert!(parse_encounter("bad", contents).is_err(), "{}", contents);
+        }
+    }
+}
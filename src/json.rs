@@ -0,0 +1,279 @@
+// just enough of a json reader for the stat block files, kept std only like the rest
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> &[Value] {
+        match self {
+            Value::Array(values) => values,
+            _ => &[],
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        position: 0,
+        depth: 0,
+    };
+    let value = parser.value()?;
+    parser.whitespace();
+    if parser.position != parser.chars.len() {
+        return Err(parser.error("Unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+// deeper nesting than any stat block needs is an error instead of a stack overflow
+const MAX_DEPTH: usize = 128;
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    // objects and arrays the parser is currently inside of
+    depth: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        message.to_string() + " at character " + &self.position.to_string()
+    }
+
+    fn whitespace(&mut self) {
+        while self
+            .chars
+            .get(self.position)
+            .is_some_and(|c| c.is_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.whitespace();
+        if self.chars.get(self.position) == Some(&expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&("Expected ".to_string() + &expected.to_string())))
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        for c in word.chars() {
+            if self.chars.get(self.position) != Some(&c) {
+                return Err(self.error("Invalid literal"));
+            }
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.whitespace();
+        match self.chars.get(self.position) {
+            Some(&c) if c == '{' || c == '[' => {
+                if self.depth == MAX_DEPTH {
+                    return Err(self.error("Nested too deeply"));
+                }
+                self.depth += 1;
+                let value = if c == '{' {
+                    self.object()
+                } else {
+                    self.array()
+                };
+                self.depth -= 1;
+                value
+            }
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('t') => self.literal("true", Value::Bool(true)),
+            Some('f') => self.literal("false", Value::Bool(false)),
+            Some('n') => self.literal("null", Value::Null),
+            Some(_) => self.number(),
+            None => Err(self.error("Unexpected end of file")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.whitespace();
+        if self.chars.get(self.position) == Some(&'}') {
+            self.position += 1;
+            return Ok(Value::Object(fields));
+        }
+        loop {
+            self.whitespace();
+            let key = self.string()?;
+            self.expect(':')?;
+            fields.push((key, self.value()?));
+            self.whitespace();
+            match self.chars.get(self.position) {
+                Some(',') => self.position += 1,
+                Some('}') => {
+                    self.position += 1;
+                    return Ok(Value::Object(fields));
+                }
+                _ => return Err(self.error("Expected , or }")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.whitespace();
+        if self.chars.get(self.position) == Some(&']') {
+            self.position += 1;
+            return Ok(Value::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.whitespace();
+            match self.chars.get(self.position) {
+                Some(',') => self.position += 1,
+                Some(']') => {
+                    self.position += 1;
+                    return Ok(Value::Array(values));
+                }
+                _ => return Err(self.error("Expected , or ]")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            let c = match self.chars.get(self.position) {
+                Some(c) => *c,
+                None => return Err(self.error("Unterminated string")),
+            };
+            self.position += 1;
+            match c {
+                '"' => return Ok(string),
+                '\\' => {
+                    let escaped = self.chars.get(self.position).copied();
+                    self.position += 1;
+                    match escaped {
+                        Some('n') => string.push('\n'),
+                        Some('t') => string.push('\t'),
+                        Some('r') => string.push('\r'),
+                        Some('b') => string.push('\u{8}'),
+                        Some('f') => string.push('\u{c}'),
+                        Some('u') => {
+                            let hex: String =
+                                self.chars.iter().skip(self.position).take(4).collect();
+                            let code = u32::from_str_radix(&hex, 16)
+                                .map_err(|_| self.error("Invalid unicode escape"))?;
+                            self.position += 4;
+                            string.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                        }
+                        Some(c) => string.push(c),
+                        None => return Err(self.error("Unterminated string")),
+                    }
+                }
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.position;
+        while self
+            .chars
+            .get(self.position)
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            self.position += 1;
+        }
+        let number: String = self.chars[start..self.position].iter().collect();
+        number
+            .parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| self.error("Invalid number"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_literals() {
+        assert_eq!(parse("true"), Ok(Value::Bool(true)));
+        assert_eq!(parse(" false "), Ok(Value::Bool(false)));
+        assert_eq!(parse("null"), Ok(Value::Null));
+        assert_eq!(parse("-1.5e2"), Ok(Value::Number(-150.0)));
+        assert_eq!(
+            parse("{\"a\": [1, \"b\"], \"c\": {}}"),
+            Ok(Value::Object(vec![
+                (
+                    "a".to_string(),
+                    Value::Array(vec![Value::Number(1.0), Value::String("b".to_string())])
+                ),
+                ("c".to_string(), Value::Object(Vec::new())),
+            ]))
+        );
+    }
+
+    #[test]
+    fn parses_escapes() {
+        assert_eq!(
+            parse(r#""a\"b\\c\nd\u00e9\/""#),
+            Ok(Value::String("a\"b\\c\nd\u{e9}/".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        for text in [
+            "",
+            "tru",
+            "{",
+            "[1,",
+            "{\"a\" 1}",
+            "\"open",
+            "\"\\u12\"",
+            "1 2",
+            "[1]]",
+        ] {
+            assert!(parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn limits_nesting() {
+        assert!(parse(&("[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH))).is_ok());
+        assert!(parse(&"[".repeat(100_000)).is_err());
+    }
+}
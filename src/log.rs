@@ -0,0 +1,209 @@
+// one processed command with enough context to write up a session recap
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    // seconds since the unix epoch
+    pub time: u64,
+    pub round: u16,
+    // whose turn it was when the command ran
+    pub turn: Option<String>,
+    // who dealt the damage or healing, given with by or the turn's entity
+    pub source: Option<String>,
+    pub command: String,
+    pub outcome: Result<String, String>,
+    // hit point change per entity, negative for damage
+    pub hp_changes: Vec<(String, i32)>,
+    // the save, encounter or campaign file the command read, replays use it instead of the disk
+    pub file: Option<String>,
+}
+
+impl LogEntry {
+    // wall clock time as HH:MM:SS in UTC
+    pub fn time_of_day(&self) -> String {
+        let seconds = self.time % 86400;
+        format!(
+            "{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        )
+    }
+
+    pub fn outcome_text(&self) -> String {
+        match &self.outcome {
+            Ok(message) => message.clone(),
+            Err(message) => "ERROR: ".to_string() + message,
+        }
+    }
+
+    pub fn hp_changes_text(&self) -> String {
+        self.hp_changes
+            .iter()
+            .map(|(name, change)| {
+                if *change > 0 {
+                    name.clone() + " +" + &change.to_string()
+                } else {
+                    name.clone() + " " + &change.to_string()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.time_of_day())?;
+        if self.round > 0 {
+            write!(f, " R{}", self.round)?;
+        }
+        if let Some(turn) = &self.turn {
+            write!(f, " {}", turn)?;
+        }
+        write!(f, ": {}", self.command)?;
+        if let Some(source) = &self.source {
+            if !self.command.to_lowercase().contains(" by ") {
+                write!(f, " (by {})", source)?;
+            }
+        }
+        if !self.hp_changes.is_empty() {
+            write!(f, " ({})", self.hp_changes_text())?;
+        }
+        Ok(())
+    }
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', "<br>")
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "<br>")
+}
+
+pub fn to_markdown(log: &[LogEntry]) -> String {
+    let mut markdown = String::from("# Combat Log\n\n");
+    markdown.push_str("| Time (UTC) | Round | Turn | Command | Source | Outcome | HP Changes |\n");
+    markdown.push_str("| --- | --- | --- | --- | --- | --- | --- |\n");
+    for entry in log {
+        let cells = [
+            entry.time_of_day(),
+            entry.round.to_string(),
+            entry.turn.clone().unwrap_or_default(),
+            "`".to_string() + &entry.command + "`",
+            entry.source.clone().unwrap_or_default(),
+            entry.outcome_text(),
+            entry.hp_changes_text(),
+        ];
+        markdown.push('|');
+        for cell in cells {
+            markdown.push(' ');
+            markdown.push_str(&markdown_cell(&cell));
+            markdown.push_str(" |");
+        }
+        markdown.push('\n');
+    }
+    markdown
+}
+
+pub fn to_html(log: &[LogEntry]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Combat Log</title>\n</head>\n<body>\n<h1>Combat Log</h1>\n<table>\n",
+    );
+    html.push_str("<tr><th>Time (UTC)</th><th>Round</th><th>Turn</th><th>Command</th><th>Source</th><th>Outcome</th><th>HP Changes</th></tr>\n");
+    for entry in log {
+        let cells = [
+            entry.time_of_day(),
+            entry.round.to_string(),
+            entry.turn.clone().unwrap_or_default(),
+            entry.command.clone(),
+            entry.source.clone().unwrap_or_default(),
+            entry.outcome_text(),
+            entry.hp_changes_text(),
+        ];
+        html.push_str("<tr>");
+        for cell in cells {
+            html.push_str("<td>");
+            html.push_str(&html_escape(&cell));
+            html.push_str("</td>");
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+// a recorded session being stepped through, position is how many commands have been run
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub seed: u64,
+    // time, command and the file it read
+    pub commands: Vec<(u64, String, Option<String>)>,
+    pub position: usize,
+}
+
+// first line is the rng seed, then one timestamped command per line, a command that
+// read a file is followed by file|<line count> and the lines of the file
+pub fn to_replay(seed: u64, commands: &[&LogEntry]) -> String {
+    let mut replay = "seed|".to_string() + &seed.to_string() + "\n";
+    for entry in commands {
+        replay.push_str(&entry.time.to_string());
+        replay.push('|');
+        replay.push_str(&entry.command);
+        replay.push('\n');
+        if let Some(file) = &entry.file {
+            replay.push_str("file|");
+            replay.push_str(&file.lines().count().to_string());
+            replay.push('\n');
+            for line in file.lines() {
+                replay.push_str(line);
+                replay.push('\n');
+            }
+        }
+    }
+    replay
+}
+
+pub fn parse_replay(contents: &str) -> Result<Replay, String> {
+    let mut lines = contents.lines();
+    let seed = match lines.next().and_then(|x| x.split_once('|')) {
+        Some(("seed", seed)) => seed
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| "Invalid seed in replay file".to_string())?,
+        _ => return Err("Replay file doesn't start with a seed".to_string()),
+    };
+    let mut commands: Vec<(u64, String, Option<String>)> = Vec::new();
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match line.split_once('|') {
+            Some(("file", count)) => {
+                let count = count
+                    .parse::<usize>()
+                    .map_err(|_| "Invalid line in replay file: ".to_string() + line)?;
+                let file = Vec::from_iter(lines.by_ref().take(count));
+                match commands.last_mut() {
+                    Some(command) if file.len() == count => command.2 = Some(file.join("\n")),
+                    _ => return Err("Invalid line in replay file: ".to_string() + line),
+                }
+            }
+            Some((time, command)) => commands.push((
+                time.parse::<u64>()
+                    .map_err(|_| "Invalid time in replay file: ".to_string() + line)?,
+                command.to_string(),
+                None,
+            )),
+            None => return Err("Invalid line in replay file: ".to_string() + line),
+        }
+    }
+    Ok(Replay {
+        seed,
+        commands,
+        position: 0,
+    })
+}
@@ -1,96 +1,438 @@
 #![windows_subsystem = "windows"]
 
+pub mod completion;
+pub mod dice;
+pub mod difficulty;
+pub mod encounter;
+pub mod json;
+pub mod log;
+pub mod server;
+pub mod tabs;
+pub mod templates;
 pub mod types;
 use eframe::egui;
+use std::io::Write;
+
+// typed commands are kept here between sessions, one per line
+const HISTORY_FILE: &str = "history.txt";
+const HISTORY_LENGTH: usize = 1000;
+
+// ctrl+r search through the history, skip counts how many older matches were passed over
+struct HistorySearch {
+    query: String,
+    skip: usize,
+    // the line being typed before searching, put back if the search is cancelled
+    original: String,
+}
 
 #[derive(Default)]
 struct Content {
-    game: types::Game,
+    tabs: tabs::Tabs,
     text: String,
     last_index: usize,
     last_entered_commands: Vec<String>,
+    // the line being typed before going back through the history
+    draft: String,
+    search: Option<HistorySearch>,
     last_result: String,
+    show_player_view: bool,
+    show_log: bool,
+    show_stats: bool,
+    // entity shown in the detail pane, with its notes and a new tag being edited
+    selected: Option<String>,
+    notes: String,
+    new_tag: String,
+    // the detail pane has the keyboard, so keys don't go to the command line
+    editing_details: bool,
+    // candidates shown in the popup, the one picked and the line it was picked for
+    completion: Option<(completion::Completion, usize, String)>,
 }
 
-const COMMANDS: [&str; 9] = [
-    "add_entity",
-    "remove_entity",
-    "add_effect",
-    "remove_effect",
-    "damage",
-    "heal",
-    "clear",
-    "save",
-    "load",
-];
-
 impl eframe::App for Content {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        for (tab, remote) in self.tabs.take_remote_commands() {
+            let result = self
+                .tabs
+                .process_remote_command(tab, remote.command.clone());
+            if tab == self.tabs.get_active() {
+                match &result {
+                    Ok(message) => self.last_result = message.clone(),
+                    Err(message) => self.last_result = "ERROR: ".to_string() + message,
+                }
+            }
+            remote.reply(result);
+        }
+
+        if self.show_player_view {
+            self.player_view(ctx);
+        }
+
+        egui::SidePanel::right("log")
+            .resizable(true)
+            .default_width(300.0)
+            .show_animated(ctx, self.show_log, |ui| {
+                ui.heading("Combat Log");
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for (index, entry) in self.tabs.game().get_log().iter().enumerate() {
+                            egui::CollapsingHeader::new(entry.to_string())
+                                .id_source(("log", index))
+                                .show(ui, |ui| {
+                                    ui.label(entry.outcome_text());
+                                });
+                        }
+                    });
+            });
+
+        egui::TopBottomPanel::bottom("stats")
+            .resizable(true)
+            .show_animated(ctx, self.show_stats, |ui| {
+                ui.heading("Encounter Stats");
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("stats_table").striped(true).show(ui, |ui| {
+                        for header in [
+                            "Name", "Dealt", "Taken", "Healed", "Kills", "Crits", "Rounds",
+                        ] {
+                            ui.strong(header);
+                        }
+                        ui.strong("Effects");
+                        ui.end_row();
+                        for entity in self.tabs.game().get_entities() {
+                            if entity.event().is_some() {
+                                continue;
+                            }
+                            let stats = entity.encounter_stats();
+                            ui.label(entity.name())
+                                .on_hover_text(entity.lifetime_stats().to_string());
+                            for value in [
+                                stats.damage_dealt,
+                                stats.damage_taken,
+                                stats.healing_done,
+                                stats.kills as u32,
+                                stats.crits as u32,
+                                stats.rounds_survived as u32,
+                            ] {
+                                ui.label(value.to_string());
+                            }
+                            ui.label(
+                                stats
+                                    .effect_uptime
+                                    .iter()
+                                    .map(|(name, seconds)| {
+                                        name.clone() + " " + &types::format_seconds(*seconds)
+                                    })
+                                    .collect::<Vec<String>>()
+                                    .join(", "),
+                            );
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+        let mut detail_commands = Vec::new();
+        let selected = self.selected.clone().and_then(|name| {
+            self.tabs
+                .game()
+                .get_entities()
+                .iter()
+                .find(|x| x.name() == name)
+                .cloned()
+        });
+        if selected.is_none() {
+            self.selected = None;
+            self.editing_details = false;
+        }
+        egui::SidePanel::left("details")
+            .resizable(true)
+            .default_width(250.0)
+            .show_animated(ctx, selected.is_some(), |ui| {
+                let Some(entity) = &selected else {
+                    return;
+                };
+                let name = entity.name();
+                ui.horizontal(|ui| {
+                    ui.heading(name);
+                    if ui.small_button("x").on_hover_text("Close").clicked() {
+                        self.selected = None;
+                    }
+                });
+                let stat_block = entity.stat_block();
+                if !stat_block.is_empty() {
+                    ui.label(stat_block);
+                }
+                ui.separator();
+                ui.label("Tags");
+                ui.horizontal_wrapped(|ui| {
+                    for tag in entity.tags() {
+                        if ui
+                            .add(egui::Button::new(tag).small().rounding(8.0))
+                            .on_hover_text("Click to remove")
+                            .clicked()
+                        {
+                            detail_commands.push("untag ".to_string() + name + " " + tag);
+                        }
+                    }
+                });
+                let tag_field = ui
+                    .horizontal(|ui| {
+                        let field = ui.text_edit_singleline(&mut self.new_tag);
+                        let entered =
+                            field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        if (ui.button("Add").clicked() || entered)
+                            && !self.new_tag.trim().is_empty()
+                        {
+                            detail_commands
+                                .push("tag ".to_string() + name + " " + self.new_tag.trim());
+                            self.new_tag.clear();
+                        }
+                        field
+                    })
+                    .inner;
+                ui.separator();
+                ui.label("Notes");
+                let notes_field = ui.add(
+                    egui::TextEdit::multiline(&mut self.notes)
+                        .desired_width(f32::INFINITY)
+                        .desired_rows(8),
+                );
+                ui.horizontal(|ui| {
+                    if ui.button("Save notes").clicked() {
+                        // kept on one line so it goes through the command log like anything else
+                        detail_commands.push(
+                            "note ".to_string()
+                                + name
+                                + " --replace "
+                                + &types::escape_notes(&self.notes),
+                        );
+                    }
+                    if ui.button("Revert").clicked() {
+                        self.notes = entity.notes().to_string();
+                    }
+                });
+                self.editing_details = tag_field.has_focus() || notes_field.has_focus();
+            });
+        for command in detail_commands {
+            let _ = self.run_command(command);
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.label("Enter Commands, use help <command> for more info");
+            ui.horizontal(|ui| {
+                ui.label("Enter Commands, use help <command> for more info");
+                ui.checkbox(&mut self.show_player_view, "Player view");
+                ui.checkbox(&mut self.show_log, "Log");
+                ui.checkbox(&mut self.show_stats, "Stats");
+            });
+
+            let mut tab_commands = Vec::new();
+            ui.horizontal(|ui| {
+                for (i, name) in self.tabs.get_names().iter().enumerate() {
+                    let tab = ui.selectable_label(i == self.tabs.get_active(), name);
+                    if tab.clicked() {
+                        tab_commands.push("tab ".to_string() + name);
+                    }
+                    tab.context_menu(|ui| {
+                        if ui.button("Close").clicked() {
+                            tab_commands.push("close_tab ".to_string() + name);
+                            ui.close_menu();
+                        }
+                    });
+                }
+                if ui.small_button("+").on_hover_text("New tab").clicked() {
+                    tab_commands.push("new_tab".to_string());
+                }
+            });
+            for command in tab_commands {
+                let _ = self.run_command(command);
+            }
 
             ui.label(self.last_result.clone());
 
+            let typing = !self.editing_details;
+            if typing && ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::R)) {
+                match &mut self.search {
+                    // searching again goes to the next older match
+                    Some(search) => {
+                        if history_match(
+                            &self.last_entered_commands,
+                            &search.query,
+                            search.skip + 1,
+                        )
+                        .is_some()
+                        {
+                            search.skip += 1;
+                        }
+                    }
+                    None => {
+                        self.search = Some(HistorySearch {
+                            query: String::new(),
+                            skip: 0,
+                            original: self.text.clone(),
+                        });
+                        self.text = String::new();
+                    }
+                }
+            }
+            if let Some(search) = &mut self.search {
+                // the text field holds the query while searching
+                if search.query != self.text {
+                    search.query = self.text.clone();
+                    search.skip = 0;
+                }
+                let found = history_match(&self.last_entered_commands, &search.query, search.skip);
+                ui.label(
+                    "(reverse-i-search) `".to_string()
+                        + &search.query
+                        + "`: "
+                        + found.as_deref().unwrap_or("no match"),
+                );
+            }
+
+            if let Some((position, length)) = self.tabs.game().get_replay() {
+                let mut step = position;
+                ui.horizontal(|ui| {
+                    ui.label("Replay");
+                    if ui.button("<").clicked() {
+                        step = position.saturating_sub(1);
+                    }
+                    ui.add(egui::Slider::new(&mut step, 0..=length));
+                    if ui.button(">").clicked() {
+                        step = (position + 1).min(length);
+                    }
+                    if ui.button("Continue from here").clicked() {
+                        let _ = self.run_command("end_replay".to_string());
+                    }
+                });
+                if step != position {
+                    let _ = self.run_command("replay_step ".to_string() + &step.to_string());
+                }
+            }
+
             let text_field = egui::TextEdit::singleline(&mut self.text)
                 .desired_width(f32::INFINITY)
                 .lock_focus(true);
 
             let output = text_field.show(ui);
 
-            if ctx.input(|i| i.key_pressed(egui::Key::Tab)) {
-                let last_word = self.text.split_whitespace().last();
-                match last_word {
-                    Some(word) => {
-                        let mut new_text: Option<String> = None;
-                        let mut matchables = self.game.get_matchable_names();
-                        for command in COMMANDS.iter() {
-                            matchables.push(command.to_string());
-                        }
-                        for name in matchables.iter() {
-                            if name.to_lowercase().starts_with(&word.to_lowercase()) {
-                                if new_text.is_some() {
-                                    new_text = None;
-                                    break;
-                                }
-                                new_text = Some(
-                                    self.text[..self.text.len() - word.len()].to_string() + name,
-                                );
-                            }
-                        }
-                        if let Some(new_text) = new_text {
-                            self.text = new_text;
-                            let text_edit_id = output.response.id;
-                            if let Some(mut state) =
-                                egui::TextEdit::load_state(ui.ctx(), text_edit_id)
-                            {
-                                let ccursor = egui::text::CCursor::new(self.text.chars().count());
-                                state
-                                    .cursor
-                                    .set_char_range(Some(egui::text::CCursorRange::one(ccursor)));
-                                state.store(ui.ctx(), text_edit_id);
-                            }
-                            output.response.request_focus();
+            // typing anything after a completion closes the popup
+            if let Some((_, _, completed)) = &self.completion {
+                if completed != &self.text {
+                    self.completion = None;
+                }
+            }
+            let mut picked = None;
+            if typing && ctx.input(|i| i.key_pressed(egui::Key::Tab)) {
+                // repeated tabs cycle through the candidates
+                match &self.completion {
+                    Some((completion, index, _)) => {
+                        let step = if ctx.input(|i| i.modifiers.shift) {
+                            completion.candidates.len() - 1
+                        } else {
+                            1
+                        };
+                        picked = Some((index + step) % completion.candidates.len());
+                    }
+                    None => {
+                        let completion = completion::complete(&mut self.tabs, &self.text);
+                        if !completion.candidates.is_empty() {
+                            let original = self.text.clone();
+                            self.completion = Some((completion, 0, original));
+                            picked = Some(0);
                         }
                     }
-                    None => (),
                 }
             }
-
-            if ctx.input(|i| i.key_pressed(egui::Key::Enter)) {
-                self.handle_enter();
+            if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+                self.completion = None;
+            }
+            if let Some((completion, index, _)) = &self.completion {
+                let popup = egui::Area::new(egui::Id::new("completion"))
+                    .order(egui::Order::Foreground)
+                    .fixed_pos(output.response.rect.left_bottom());
+                popup.show(ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        egui::ScrollArea::vertical()
+                            .max_height(200.0)
+                            .show(ui, |ui| {
+                                for (i, candidate) in completion.candidates.iter().enumerate() {
+                                    let label = ui.selectable_label(i == *index, candidate);
+                                    if i == *index && picked.is_some() {
+                                        label.scroll_to_me(None);
+                                    }
+                                    if label.clicked() {
+                                        picked = Some(i);
+                                    }
+                                }
+                            });
+                    });
+                });
             }
-            if ctx.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
-                self.handle_arrow_up();
+            if let (Some(picked), Some((completion, index, completed))) =
+                (picked, &mut self.completion)
+            {
+                self.text = completion.apply(&self.text, picked);
+                *index = picked;
+                *completed = self.text.clone();
+                if completion.candidates.len() == 1 {
+                    self.completion = None;
+                }
+                let text_edit_id = output.response.id;
+                if let Some(mut state) = egui::TextEdit::load_state(ui.ctx(), text_edit_id) {
+                    let ccursor = egui::text::CCursor::new(self.text.chars().count());
+                    state
+                        .cursor
+                        .set_char_range(Some(egui::text::CCursorRange::one(ccursor)));
+                    state.store(ui.ctx(), text_edit_id);
+                }
+                output.response.request_focus();
             }
-            if ctx.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
-                self.handle_arrow_down();
+
+            if let Some(search) = &self.search {
+                let found = history_match(&self.last_entered_commands, &search.query, search.skip);
+                if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    self.text = search.original.clone();
+                    self.search = None;
+                } else if ctx.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    self.search = None;
+                    if let Some(found) = found {
+                        self.text = found;
+                        self.handle_enter();
+                    }
+                } else if ctx.input(|i| {
+                    i.key_pressed(egui::Key::ArrowUp)
+                        || i.key_pressed(egui::Key::ArrowDown)
+                        || i.key_pressed(egui::Key::ArrowLeft)
+                        || i.key_pressed(egui::Key::ArrowRight)
+                }) {
+                    // moving around takes the match into the line for editing
+                    self.text = found.unwrap_or(search.original.clone());
+                    self.search = None;
+                }
+            } else if typing {
+                if ctx.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    self.handle_enter();
+                }
+                if ctx.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
+                    self.handle_arrow_up();
+                }
+                if ctx.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
+                    self.handle_arrow_down();
+                }
             }
 
+            let mut commands = Vec::new();
             egui::ScrollArea::vertical().show(ui, |ui| {
-                ui.label(self.game.get_entities_list());
+                commands = self.entity_table(ui);
             });
+            for command in commands {
+                // the outcome is shown through last_result
+                let _ = self.run_command(command);
+            }
 
-            output.response.request_focus();
+            if typing {
+                output.response.request_focus();
+            }
         });
     }
 }
@@ -98,17 +440,292 @@ impl eframe::App for Content {
 impl Content {
     fn handle_enter(&mut self) {
         self.last_index = 0;
-        let result = self.game.process_command(self.text.clone());
-        if result.is_err() {
-            self.last_result = "ERROR: ".to_string() + &result.err().unwrap();
-        } else {
-            self.last_result = result.ok().unwrap().to_string();
-        }
-        self.last_entered_commands.push(self.text.clone());
+        self.draft = String::new();
+        self.remember(self.text.clone());
+        let _ = self.run_command(self.text.clone());
         self.text = String::new();
     }
 
+    // runs a command typed, generated by the ui or sent through the api
+    fn run_command(&mut self, command: String) -> Result<String, String> {
+        let result = self.tabs.process_command(command.clone());
+        match &result {
+            Ok(message) => self.last_result = message.clone(),
+            Err(message) => self.last_result = "ERROR: ".to_string() + message,
+        }
+        result
+    }
+
+    // adds a typed command to the history and the history file, repeats are only kept once,
+    // api tokens are hidden before either sees them
+    fn remember(&mut self, command: String) {
+        let command = types::redact(command.trim());
+        if command.is_empty() || self.last_entered_commands.last() == Some(&command) {
+            return;
+        }
+        if let Ok(mut file) = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(HISTORY_FILE)
+        {
+            let _ = writeln!(file, "{}", command);
+        }
+        self.last_entered_commands.push(command);
+    }
+
+    // second window for a table facing monitor, without anything the players shouldn't see
+    fn player_view(&mut self, ctx: &egui::Context) {
+        let text = self.tabs.game().get_player_view();
+        let mut closed = false;
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("player_view"),
+            egui::ViewportBuilder::default()
+                .with_title("Status Tracker - Players")
+                .with_inner_size(egui::Vec2 { x: 600.0, y: 600.0 }),
+            |ctx, _class| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        ui.label(egui::RichText::new(text).size(22.0));
+                    });
+                });
+                closed = ctx.input(|i| i.viewport().close_requested());
+            },
+        );
+        if closed {
+            self.show_player_view = false;
+        }
+    }
+
+    // one row per entity, anything clicked comes back as the equivalent command
+    fn entity_table(&mut self, ui: &mut egui::Ui) -> Vec<String> {
+        let mut commands = Vec::new();
+        let tab_names = self.tabs.get_names();
+        let active_tab = self.tabs.get_active();
+        let game = self.tabs.game();
+        if let Some(encounter) = game.get_encounter() {
+            let mut details = encounter.notes.join("\n");
+            if let Some(map) = &encounter.map {
+                details = "Map: ".to_string() + map + "\n" + &details;
+            }
+            ui.label("Encounter: ".to_string() + &encounter.name)
+                .on_hover_text(details);
+        }
+        if let Some(campaign) = game.get_campaign() {
+            ui.label("Campaign: ".to_string() + campaign);
+        }
+        if let Some(difficulty) = game.get_difficulty() {
+            ui.label("Difficulty: ".to_string() + &difficulty);
+        }
+        let turn = game.get_turn().cloned();
+        if let Some(turn) = &turn {
+            ui.label(
+                "Round ".to_string() + &game.get_round().to_string() + ", " + turn + "'s turn",
+            );
+        }
+        let mut selected = None;
+        let entities = game.get_entities().to_vec();
+        let in_turn_order = game.in_turn_order();
+        egui::Grid::new("entities")
+            .striped(true)
+            .min_col_width(0.0)
+            .show(ui, |ui| {
+                for (position, entity) in entities.iter().enumerate() {
+                    let name = entity.name();
+                    let is_turn = turn.as_deref() == Some(name);
+                    let marker = if is_turn { ">" } else { "" };
+                    // rows can be dragged onto each other to change the turn order
+                    let handle = if in_turn_order {
+                        let id = egui::Id::new(("turn_order", name));
+                        ui.dnd_drag_source(id, name.to_string(), |ui| {
+                            ui.label(if is_turn { ">" } else { "≡" })
+                        })
+                        .response
+                    } else {
+                        ui.label(marker)
+                    };
+                    let [r, g, b] = game.faction(entity.team()).color();
+                    let mut label =
+                        egui::RichText::new(name).color(egui::Color32::from_rgb(r, g, b));
+                    if is_turn {
+                        label = label.strong();
+                    }
+                    let mut hover = game.faction(entity.team()).to_string();
+                    let stat_block = entity.stat_block();
+                    if !stat_block.is_empty() {
+                        hover += "\n";
+                        hover += &stat_block;
+                    }
+                    if entity.hidden() {
+                        label = label.italics();
+                        hover += ", hidden from players";
+                    }
+                    if !entity.tags().is_empty() {
+                        hover += "\nTags: ";
+                        hover += &entity.tags().join(", ");
+                    }
+                    if !entity.notes().is_empty() {
+                        hover += "\n";
+                        hover += entity.notes();
+                    }
+                    let name_response = ui
+                        .add(egui::Label::new(label).sense(egui::Sense::click()))
+                        .on_hover_text(hover);
+                    if name_response.clicked() {
+                        selected = Some((name.to_string(), entity.notes().to_string()));
+                    }
+                    if let Some(dragged) = handle
+                        .dnd_release_payload::<String>()
+                        .or_else(|| name_response.dnd_release_payload::<String>())
+                    {
+                        commands.push(
+                            "move_turn ".to_string() + &dragged + " " + &(position + 1).to_string(),
+                        );
+                    }
+                    if let Some(reminder) = entity.event() {
+                        ui.label(
+                            entity
+                                .initiative()
+                                .map(|x| x.to_string())
+                                .unwrap_or_default(),
+                        );
+                        ui.label(reminder);
+                        name_response.context_menu(|ui| {
+                            if ui.button("Remove").clicked() {
+                                commands.push("remove_entity ".to_string() + name);
+                                ui.close_menu();
+                            }
+                        });
+                        ui.end_row();
+                        continue;
+                    }
+                    ui.label(
+                        entity
+                            .initiative()
+                            .map(|x| x.to_string())
+                            .unwrap_or_default(),
+                    );
+                    match entity.max_hp() {
+                        Some(max_hp) => {
+                            let hp = max_hp.saturating_sub(entity.damage_taken());
+                            ui.add(
+                                egui::ProgressBar::new(hp as f32 / max_hp.max(1) as f32)
+                                    .desired_width(120.0)
+                                    .text(hp.to_string() + "/" + &max_hp.to_string()),
+                            );
+                        }
+                        None => {
+                            ui.label(
+                                "Damage Taken: ".to_string() + &entity.damage_taken().to_string(),
+                            );
+                        }
+                    }
+                    ui.horizontal(|ui| {
+                        for (amount, command) in
+                            [(5, "damage"), (1, "damage"), (1, "heal"), (5, "heal")]
+                        {
+                            let text = if command == "damage" { "-" } else { "+" }.to_string()
+                                + &amount.to_string();
+                            if ui.small_button(text).clicked() {
+                                commands.push(
+                                    command.to_string() + " " + &amount.to_string() + " " + name,
+                                );
+                            }
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        for effect in entity.status_effects() {
+                            let mut text = egui::RichText::new(effect.name());
+                            if effect.secret() {
+                                text = text.italics();
+                            }
+                            let chip = ui
+                                .add(egui::Button::new(text).small().rounding(8.0))
+                                .on_hover_text(effect.duration());
+                            chip.context_menu(|ui| {
+                                if ui.button("Remove ".to_string() + effect.name()).clicked() {
+                                    commands.push(
+                                        "remove_effect ".to_string() + effect.name() + " " + name,
+                                    );
+                                    ui.close_menu();
+                                }
+                            });
+                        }
+                    });
+                    ui.label(if turn.is_some() {
+                        entity.actions()
+                    } else {
+                        String::new()
+                    });
+                    ui.label(entity.resources());
+                    name_response.context_menu(|ui| {
+                        for amount in [1, 5, 10] {
+                            if ui
+                                .button("Damage ".to_string() + &amount.to_string())
+                                .clicked()
+                            {
+                                commands
+                                    .push("damage ".to_string() + &amount.to_string() + " " + name);
+                                ui.close_menu();
+                            }
+                        }
+                        for amount in [1, 5, 10] {
+                            if ui
+                                .button("Heal ".to_string() + &amount.to_string())
+                                .clicked()
+                            {
+                                commands
+                                    .push("heal ".to_string() + &amount.to_string() + " " + name);
+                                ui.close_menu();
+                            }
+                        }
+                        ui.separator();
+                        for action in ["action", "bonus", "reaction", "movement"] {
+                            if ui.button("Spend ".to_string() + action).clicked() {
+                                commands.push("spend ".to_string() + action + " " + name);
+                                ui.close_menu();
+                            }
+                        }
+                        ui.separator();
+                        let (text, command) = if entity.hidden() {
+                            ("Reveal to players", "reveal ")
+                        } else {
+                            ("Hide from players", "hide ")
+                        };
+                        if ui.button(text).clicked() {
+                            commands.push(command.to_string() + name);
+                            ui.close_menu();
+                        }
+                        if ui.button("Remove").clicked() {
+                            commands.push("remove_entity ".to_string() + name);
+                            ui.close_menu();
+                        }
+                        if tab_names.len() > 1 {
+                            ui.separator();
+                        }
+                        for (i, tab) in tab_names.iter().enumerate() {
+                            if i != active_tab && ui.button("Move to ".to_string() + tab).clicked()
+                            {
+                                commands.push("move_to_tab ".to_string() + tab + " " + name);
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        // clicking a name opens it in the detail pane
+        if let Some((name, notes)) = selected {
+            self.selected = Some(name);
+            self.notes = notes;
+            self.new_tag.clear();
+        }
+        commands
+    }
+
     fn handle_arrow_up(&mut self) {
+        if self.last_index == 0 {
+            self.draft = self.text.clone();
+        }
         if self.last_index < self.last_entered_commands.len() {
             self.last_index += 1;
         }
@@ -129,17 +746,62 @@ impl Content {
                 .clone()
         }
         if self.last_index == 0 {
-            self.text = " ".to_string();
+            self.text = self.draft.clone();
         }
     }
 }
 
+// the history entry containing the query, skipping that many newer ones
+fn history_match(history: &[String], query: &str, skip: usize) -> Option<String> {
+    let query = query.to_lowercase();
+    history
+        .iter()
+        .rev()
+        .filter(|x| x.to_lowercase().contains(&query))
+        .nth(skip)
+        .cloned()
+}
+
+// history from earlier sessions, trimmed down to the newest entries
+fn load_history() -> Vec<String> {
+    let Ok(contents) = std::fs::read_to_string(HISTORY_FILE) else {
+        return Vec::new();
+    };
+    let mut history: Vec<String> = Vec::new();
+    for line in contents.lines().filter(|x| !x.trim().is_empty()) {
+        if history.last().map(|x| x.as_str()) != Some(line) {
+            history.push(line.to_string());
+        }
+    }
+    if history.len() > HISTORY_LENGTH {
+        history.drain(..history.len() - HISTORY_LENGTH);
+        let _ = std::fs::write(HISTORY_FILE, history.join("\n") + "\n");
+    }
+    history
+}
+
 fn main() -> eframe::Result {
     let mut options = eframe::NativeOptions::default();
-    options.viewport = options.viewport.with_title("Status Tracker").with_inner_size(egui::Vec2 { x: 1000.0, y: 600.0 });
+    options.viewport = options
+        .viewport
+        .with_title("Status Tracker")
+        .with_inner_size(egui::Vec2 {
+            x: 1000.0,
+            y: 600.0,
+        });
     eframe::run_native(
         "Status Tracker",
         options,
-        Box::new(|_cc| Ok(Box::<Content>::default())),
+        Box::new(|cc| {
+            let mut content = Content {
+                last_entered_commands: load_history(),
+                ..Content::default()
+            };
+            let ctx = cc.egui_ctx.clone();
+            content
+                .tabs
+                .set_waker(std::sync::Arc::new(move || ctx.request_repaint()));
+            Ok(Box::new(content))
+        }),
     )
 }
@@ -0,0 +1,537 @@
+use crate::json;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Status Tracker</title>
+<style>
+body { font-family: sans-serif; background: #1b1b1b; color: #ddd; margin: 1em; }
+.entity { padding: 0.4em; border-bottom: 1px solid #333; }
+.turn { background: #333; font-weight: bold; }
+.effects { color: #aaa; font-size: 0.9em; }
+</style>
+</head>
+<body>
+<h2 id="round"></h2>
+<div id="entities"></div>
+<script>
+function render(state) {
+    document.getElementById("round").textContent =
+        state.round > 0 ? "Round " + state.round + (state.turn ? ", " + state.turn + "'s turn" : "") : "";
+    const list = document.getElementById("entities");
+    list.innerHTML = "";
+    for (const entity of state.entities) {
+        const row = document.createElement("div");
+        row.className = entity.turn ? "entity turn" : "entity";
+        const name = document.createElement("span");
+        name.style.color = entity.color;
+        name.textContent = entity.name;
+        row.appendChild(name);
+        const details = document.createElement("span");
+        details.textContent = entity.event !== null ? ": " + entity.event : " " + entity.health;
+        row.appendChild(details);
+        if (entity.effects.length > 0) {
+            const effects = document.createElement("div");
+            effects.className = "effects";
+            effects.textContent = entity.effects.join(", ");
+            row.appendChild(effects);
+        }
+        list.appendChild(row);
+    }
+}
+fetch("/state").then(response => response.json()).then(render);
+new EventSource("/events").onmessage = message => render(JSON.parse(message.data));
+</script>
+</body>
+</html>
+"#;
+
+// latest public state, the version goes up every time it is published
+#[derive(Default)]
+struct Snapshot {
+    version: u64,
+    json: String,
+}
+
+// read only view of the game for phones and stream overlays, serves the page on /,
+// the state as json on /state and pushes every update as server sent events on /events
+#[derive(Clone)]
+pub struct Server {
+    port: u16,
+    snapshot: Arc<(Mutex<Snapshot>, Condvar)>,
+    stopped: Arc<AtomicBool>,
+    // joined on stop so the port is free again once it returns
+    accepting: Arc<Mutex<Option<std::thread::JoinHandle<()>>>>,
+}
+
+impl Server {
+    // lan lets phones on the same network watch, otherwise only localhost can
+    pub fn start(port: u16, json: String, lan: bool) -> Result<Server, String> {
+        let address = if lan { "0.0.0.0" } else { "127.0.0.1" };
+        let listener = TcpListener::bind((address, port)).map_err(|e| e.to_string())?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let server = Server {
+            port: listener.local_addr().map_err(|e| e.to_string())?.port(),
+            snapshot: Arc::new((Mutex::new(Snapshot { version: 0, json }), Condvar::new())),
+            stopped: Arc::new(AtomicBool::new(false)),
+            accepting: Arc::new(Mutex::new(None)),
+        };
+        let accepting = server.clone();
+        *server.accepting.lock().unwrap() =
+            Some(std::thread::spawn(move || accepting.accept(listener)));
+        Ok(server)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn publish(&self, json: String) {
+        let (lock, changed) = &*self.snapshot;
+        let mut snapshot = lock.lock().unwrap();
+        if snapshot.json != json {
+            snapshot.json = json;
+            snapshot.version += 1;
+            changed.notify_all();
+        }
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        self.snapshot.1.notify_all();
+        if let Some(accepting) = self.accepting.lock().unwrap().take() {
+            let _ = accepting.join();
+        }
+    }
+
+    fn accept(&self, listener: TcpListener) {
+        while !self.stopped.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let server = self.clone();
+                    std::thread::spawn(move || server.handle(stream));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                }
+                Err(_) => break,
+            }
+        }
+    }
+
+    fn handle(&self, mut stream: TcpStream) {
+        if !blocking_with_timeouts(&stream) {
+            return;
+        }
+        let request = match read_request(&mut stream) {
+            Some(request) => request,
+            None => return,
+        };
+        let _ = match request.path.as_str() {
+            "/" => respond(&mut stream, "200 OK", "text/html; charset=utf-8", PAGE),
+            "/state" => {
+                let json = self.snapshot.0.lock().unwrap().json.clone();
+                respond(&mut stream, "200 OK", "application/json", &json)
+            }
+            "/events" => self.stream_events(&mut stream),
+            _ => respond(&mut stream, "404 Not Found", "text/plain", "Not found"),
+        };
+    }
+
+    fn stream_events(&self, stream: &mut TcpStream) -> std::io::Result<()> {
+        stream.write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nAccess-Control-Allow-Origin: *\r\n\r\n",
+        )?;
+        let (lock, changed) = &*self.snapshot;
+        // nothing has been seen yet so the current state goes out straight away
+        let mut seen = u64::MAX;
+        loop {
+            let snapshot = lock.lock().unwrap();
+            let (snapshot, _) = changed
+                .wait_timeout_while(snapshot, std::time::Duration::from_secs(15), |x| {
+                    x.version == seen && !self.stopped.load(Ordering::Relaxed)
+                })
+                .unwrap();
+            if self.stopped.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            // a comment line keeps idle connections from timing out
+            let message = if snapshot.version == seen {
+                ": keep-alive\n\n".to_string()
+            } else {
+                "data: ".to_string() + &snapshot.json + "\n\n"
+            };
+            seen = snapshot.version;
+            drop(snapshot);
+            stream.write_all(message.as_bytes())?;
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    head: String,
+    body: String,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.head.lines().find_map(|x| {
+            let (key, value) = x.split_once(':')?;
+            if key.eq_ignore_ascii_case(name) {
+                Some(value.trim())
+            } else {
+                None
+            }
+        })
+    }
+}
+
+// clients that stop reading or writing are dropped instead of holding a thread forever
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+// requests are a line of headers and a short command at most
+const MAX_REQUEST: usize = 16 * 1024;
+
+fn blocking_with_timeouts(stream: &TcpStream) -> bool {
+    stream.set_nonblocking(false).is_ok()
+        && stream.set_read_timeout(Some(TIMEOUT)).is_ok()
+        && stream.set_write_timeout(Some(TIMEOUT)).is_ok()
+}
+
+// the whole request has to arrive within the timeout, not just each piece of it
+fn read_request(stream: &mut TcpStream) -> Option<Request> {
+    let started = std::time::Instant::now();
+    let mut bytes = Vec::new();
+    let mut buffer = [0; 1024];
+    let header_end = loop {
+        if started.elapsed() > TIMEOUT {
+            return None;
+        }
+        let read = stream.read(&mut buffer).ok()?;
+        if read == 0 {
+            return None;
+        }
+        bytes.extend_from_slice(&buffer[..read]);
+        if let Some(end) = bytes.windows(4).position(|x| x == b"\r\n\r\n") {
+            break end + 4;
+        }
+        if bytes.len() > MAX_REQUEST {
+            return None;
+        }
+    };
+    let head = String::from_utf8_lossy(&bytes[..header_end]).to_string();
+    let mut parts = head.split_whitespace();
+    let mut request = Request {
+        method: parts.next()?.to_string(),
+        path: parts.next()?.to_string(),
+        head: head.clone(),
+        body: String::new(),
+    };
+    let content_length = request
+        .header("content-length")
+        .and_then(|x| x.parse::<usize>().ok())
+        .unwrap_or(0);
+    if content_length > MAX_REQUEST {
+        return None;
+    }
+    while bytes.len() < header_end + content_length {
+        if started.elapsed() > TIMEOUT {
+            return None;
+        }
+        let read = stream.read(&mut buffer).ok()?;
+        if read == 0 {
+            return None;
+        }
+        bytes.extend_from_slice(&buffer[..read]);
+    }
+    request.body =
+        String::from_utf8_lossy(&bytes[header_end..header_end + content_length]).to_string();
+    Some(request)
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> std::io::Result<()> {
+    let response = "HTTP/1.1 ".to_string()
+        + status
+        + "\r\nContent-Type: "
+        + content_type
+        + "\r\nContent-Length: "
+        + &body.len().to_string()
+        + "\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n"
+        + body;
+    stream.write_all(response.as_bytes())
+}
+
+// escapes a string and wraps it in quotes
+pub fn json_string(string: &str) -> String {
+    let mut json = String::from('"');
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+// 16 bytes from the os random source as hex
+pub fn new_token() -> String {
+    let mut bytes = [0u8; 16];
+    let urandom = std::fs::File::open("/dev/urandom").and_then(|mut x| x.read_exact(&mut bytes));
+    if urandom.is_err() {
+        // RandomState keys come from the os random source too, e.g. on windows
+        use std::hash::{BuildHasher, Hasher};
+        for chunk in bytes.chunks_mut(8) {
+            let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+            hasher.write_usize(chunk.as_ptr() as usize);
+            chunk.copy_from_slice(&hasher.finish().to_le_bytes());
+        }
+    }
+    bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+// compares every byte so the time taken doesn't give away how much of the token matched
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+// a command sent through the api, waiting on the game to run it
+pub struct RemoteCommand {
+    pub command: String,
+    reply: mpsc::Sender<Result<String, String>>,
+}
+
+impl RemoteCommand {
+    pub fn reply(self, result: Result<String, String>) {
+        // the client may have given up waiting already
+        let _ = self.reply.send(result);
+    }
+}
+
+// lets other tools run commands with POST /command {"command": "damage 5 Goblin"}
+// and an "Authorization: Bearer <token>" header, commands are queued and run one
+// at a time by whoever owns the game
+#[derive(Clone)]
+pub struct CommandApi {
+    port: u16,
+    token: String,
+    stopped: Arc<AtomicBool>,
+    accepting: Arc<Mutex<Option<std::thread::JoinHandle<()>>>>,
+    sender: mpsc::Sender<RemoteCommand>,
+    receiver: Arc<Mutex<mpsc::Receiver<RemoteCommand>>>,
+    // called when a command comes in so the ui doesn't wait for its next frame
+    waker: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+impl CommandApi {
+    // lan opens the api up to other machines instead of only localhost
+    pub fn start(
+        port: u16,
+        token: String,
+        lan: bool,
+        waker: Option<Arc<dyn Fn() + Send + Sync>>,
+    ) -> Result<CommandApi, String> {
+        let address = if lan { "0.0.0.0" } else { "127.0.0.1" };
+        let listener = TcpListener::bind((address, port)).map_err(|e| e.to_string())?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let (sender, receiver) = mpsc::channel();
+        let api = CommandApi {
+            port: listener.local_addr().map_err(|e| e.to_string())?.port(),
+            token,
+            stopped: Arc::new(AtomicBool::new(false)),
+            accepting: Arc::new(Mutex::new(None)),
+            sender,
+            receiver: Arc::new(Mutex::new(receiver)),
+            waker,
+        };
+        let accepting = api.clone();
+        *api.accepting.lock().unwrap() =
+            Some(std::thread::spawn(move || accepting.accept(listener)));
+        Ok(api)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(accepting) = self.accepting.lock().unwrap().take() {
+            let _ = accepting.join();
+        }
+    }
+
+    // everything that came in since the last call
+    pub fn take_commands(&self) -> Vec<RemoteCommand> {
+        self.receiver.lock().unwrap().try_iter().collect()
+    }
+
+    fn accept(&self, listener: TcpListener) {
+        while !self.stopped.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let api = self.clone();
+                    std::thread::spawn(move || api.handle(stream));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                }
+                Err(_) => break,
+            }
+        }
+    }
+
+    fn handle(&self, mut stream: TcpStream) {
+        if !blocking_with_timeouts(&stream) {
+            return;
+        }
+        let request = match read_request(&mut stream) {
+            Some(request) => request,
+            None => return,
+        };
+        let (status, body) = self.respond_to(request);
+        let _ = respond(&mut stream, status, "application/json", &body);
+    }
+
+    fn respond_to(&self, request: Request) -> (&'static str, String) {
+        let error =
+            |message: &str| "{\"ok\":false,\"error\":".to_string() + &json_string(message) + "}";
+        if request.method != "POST" || request.path != "/command" {
+            return ("404 Not Found", error("Use POST /command"));
+        }
+        let given = request
+            .header("authorization")
+            .and_then(|x| x.strip_prefix("Bearer "));
+        if !given.is_some_and(|x| same_token(x, &self.token)) {
+            return ("401 Unauthorized", error("Missing or wrong token"));
+        }
+        let body = json::parse(&request.body).ok();
+        let command = match body.as_ref().and_then(|x| x.get("command")?.as_str()) {
+            Some(command) => command.to_string(),
+            None => return ("400 Bad Request", error("Expected {\"command\": \"...\"}")),
+        };
+        let (reply, result) = mpsc::channel();
+        if self.sender.send(RemoteCommand { command, reply }).is_err() {
+            return ("503 Service Unavailable", error("Game isn't running"));
+        }
+        if let Some(waker) = &self.waker {
+            waker();
+        }
+        match result.recv_timeout(std::time::Duration::from_secs(10)) {
+            Ok(Ok(result)) => (
+                "200 OK",
+                "{\"ok\":true,\"result\":".to_string() + &json_string(&result) + "}",
+            ),
+            Ok(Err(message)) => ("200 OK", error(&message)),
+            Err(_) => ("504 Gateway Timeout", error("Game didn't answer in time")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(port: u16, path: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream
+            .write_all(("GET ".to_string() + path + " HTTP/1.1\r\n\r\n").as_bytes())
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_the_latest_state() {
+        let server = Server::start(0, "{\"round\":1}".to_string(), false).unwrap();
+        assert!(get(server.port(), "/state").ends_with("\r\n\r\n{\"round\":1}"));
+        server.publish("{\"round\":2}".to_string());
+        let response = get(server.port(), "/state");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("\r\n\r\n{\"round\":2}"));
+        server.stop();
+    }
+
+    fn post(port: u16, token: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let request = "POST /command HTTP/1.1\r\nAuthorization: Bearer ".to_string()
+            + token
+            + "\r\nContent-Length: "
+            + &body.len().to_string()
+            + "\r\n\r\n"
+            + body;
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn tokens_are_random_hex() {
+        let token = new_token();
+        assert_eq!(token.len(), 32);
+        assert!(token.chars().all(|x| x.is_ascii_hexdigit()));
+        assert_ne!(token, new_token());
+    }
+
+    #[test]
+    fn api_checks_the_token_and_body() {
+        let api = CommandApi::start(0, "secret".to_string(), false, None).unwrap();
+        assert!(post(api.port(), "wrong", "{\"command\": \"next\"}").contains("401"));
+        assert!(post(api.port(), "secre", "{\"command\": \"next\"}").contains("401"));
+        assert!(post(api.port(), "secret", "{\"cmd\": \"next\"}").contains("400"));
+        assert!(post(api.port(), "secret", "{\"command\": 5}").contains("400"));
+        api.stop();
+    }
+
+    #[test]
+    fn lan_servers_still_answer_locally() {
+        let server = Server::start(0, "{}".to_string(), true).unwrap();
+        assert!(get(server.port(), "/state").ends_with("\r\n\r\n{}"));
+        server.stop();
+    }
+
+    #[test]
+    fn oversized_requests_are_dropped() {
+        let server = Server::start(0, "{}".to_string(), false).unwrap();
+        let mut stream = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+        let request = "GET /state HTTP/1.1\r\nX-Padding: ".to_string() + &"a".repeat(MAX_REQUEST);
+        let _ = stream.write_all(request.as_bytes());
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        assert_eq!(response, "");
+        server.stop();
+    }
+
+    #[test]
+    fn port_is_free_again_after_stopping() {
+        let server = Server::start(0, String::new(), false).unwrap();
+        let port = server.port();
+        server.stop();
+        let server = Server::start(port, String::new(), false).unwrap();
+        server.stop();
+    }
+}
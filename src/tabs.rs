@@ -0,0 +1,361 @@
+use crate::types;
+
+// games run side by side for split party scenes, each with its own initiative and round,
+// commands go to the one being shown
+pub struct Tabs {
+    // name and game of every tab, in the order they're shown
+    tabs: Vec<(String, types::Game)>,
+    active: usize,
+    // handed to every game so remote commands repaint the ui
+    waker: Option<std::sync::Arc<dyn Fn() + Send + Sync>>,
+}
+
+impl Default for Tabs {
+    fn default() -> Self {
+        Tabs {
+            tabs: vec![("Main".to_string(), types::Game::default())],
+            active: 0,
+            waker: None,
+        }
+    }
+}
+
+// a tab save is every tab's save one after another:
+// tab|<name>|<number of lines>
+// <that many lines in the save file format>
+// active|<index of the tab being shown>
+impl Tabs {
+    pub fn game(&mut self) -> &mut types::Game {
+        &mut self.tabs[self.active].1
+    }
+
+    pub fn get_names(&self) -> Vec<String> {
+        self.tabs.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    pub fn get_active(&self) -> usize {
+        self.active
+    }
+
+    pub fn set_waker(&mut self, waker: std::sync::Arc<dyn Fn() + Send + Sync>) {
+        for (_, game) in self.tabs.iter_mut() {
+            game.set_waker(waker.clone());
+        }
+        self.waker = Some(waker);
+    }
+
+    // remote commands from every tab's api, with the index of the tab they were sent to
+    pub fn take_remote_commands(&mut self) -> Vec<(usize, crate::server::RemoteCommand)> {
+        let mut commands = Vec::new();
+        for (i, (_, game)) in self.tabs.iter_mut().enumerate() {
+            commands.extend(game.take_remote_commands().into_iter().map(|x| (i, x)));
+        }
+        commands
+    }
+
+    // runs a remote command on the tab whose api it came in through, shown or not
+    pub fn process_remote_command(
+        &mut self,
+        tab: usize,
+        command: String,
+    ) -> Result<String, String> {
+        types::check_remote_command(&command)?;
+        match self.tabs.get_mut(tab) {
+            Some((_, game)) => game.process_command(command),
+            None => Err("That tab was closed".to_string()),
+        }
+    }
+
+    // a tab by name or by its number, counting from 1
+    fn find(&self, name: &str) -> Result<usize, String> {
+        if let Some(index) = self
+            .tabs
+            .iter()
+            .position(|x| x.0.eq_ignore_ascii_case(name))
+        {
+            return Ok(index);
+        }
+        match name.parse::<usize>() {
+            Ok(number) if (1..=self.tabs.len()).contains(&number) => Ok(number - 1),
+            _ => Err("No tab named ".to_string() + name),
+        }
+    }
+
+    fn check_name(&self, name: &str) -> Result<(), String> {
+        if self.tabs.iter().any(|x| x.0.eq_ignore_ascii_case(name)) {
+            return Err("There's already a tab named ".to_string() + name);
+        }
+        if name.contains('|') {
+            return Err("Tab names can't contain |".to_string());
+        }
+        Ok(())
+    }
+
+    fn new_game(&self) -> types::Game {
+        let mut game = types::Game::default();
+        if let Some(waker) = &self.waker {
+            game.set_waker(waker.clone());
+        }
+        game
+    }
+
+    fn list(&self) -> String {
+        let mut list = String::new();
+        for (i, (name, _)) in self.tabs.iter().enumerate() {
+            if i > 0 {
+                list.push('\n');
+            }
+            list.push_str(if i == self.active { "> " } else { "  " });
+            list.push_str(&(i + 1).to_string());
+            list.push_str(". ");
+            list.push_str(name);
+        }
+        list
+    }
+
+    fn save(&mut self, filename: &str) -> Result<String, String> {
+        types::check_file_name(filename)?;
+        std::fs::create_dir_all(types::TAB_DIRECTORY).map_err(|e| e.to_string())?;
+        std::fs::write(
+            types::TAB_DIRECTORY.to_string() + "/" + filename + ".txt",
+            self.save_text(),
+        )
+        .map_err(|e| e.to_string())?;
+        Ok("Saved ".to_string() + &self.tabs.len().to_string() + " tabs to " + filename)
+    }
+
+    fn save_text(&self) -> String {
+        let mut string = String::new();
+        for (name, game) in self.tabs.iter() {
+            let text = game.to_save_text();
+            string.push_str("tab|");
+            string.push_str(name);
+            string.push('|');
+            string.push_str(&text.lines().count().to_string());
+            string.push('\n');
+            string.push_str(&text);
+        }
+        string.push_str("active|");
+        string.push_str(&self.active.to_string());
+        string.push('\n');
+        string
+    }
+
+    // replaces every tab with the saved ones, the servers of the old ones are stopped
+    fn load(&mut self, filename: &str) -> Result<String, String> {
+        types::check_file_name(filename)?;
+        let contents =
+            std::fs::read_to_string(types::TAB_DIRECTORY.to_string() + "/" + filename + ".txt")
+                .map_err(|e| e.to_string())?;
+        self.load_text(&contents)?;
+        Ok("Loaded ".to_string() + &self.tabs.len().to_string() + " tabs from " + filename)
+    }
+
+    fn load_text(&mut self, contents: &str) -> Result<(), String> {
+        let invalid = || "Invalid tab save".to_string();
+        let mut tabs = Vec::new();
+        let mut active = 0;
+        let mut lines = contents.lines();
+        while let Some(line) = lines.next() {
+            let line = line.split('|').collect::<Vec<&str>>();
+            match (line[0], line.len()) {
+                ("tab", 3) => {
+                    let count = line[2].parse::<usize>().map_err(|_| invalid())?;
+                    let text = Vec::from_iter(lines.by_ref().take(count)).join("\n");
+                    let mut game = self.new_game();
+                    game.load_save_text(&text)
+                        .map_err(|e| "Tab ".to_string() + line[1] + ": " + &e)?;
+                    tabs.push((line[1].to_string(), game));
+                }
+                ("active", 2) => active = line[1].parse().map_err(|_| invalid())?,
+                _ => return Err(invalid()),
+            }
+        }
+        if tabs.is_empty() {
+            return Err(invalid());
+        }
+        for (_, game) in self.tabs.iter_mut() {
+            game.stop_servers();
+        }
+        self.active = active.min(tabs.len() - 1);
+        self.tabs = tabs;
+        Ok(())
+    }
+
+    // moves entities from the tab being shown, they keep their hit points, effects and initiative
+    fn move_entities(&mut self, tab: &str, names: &[&str]) -> Result<String, String> {
+        let target = self.find(tab)?;
+        if target == self.active {
+            return Err("Already in ".to_string() + &self.tabs[target].0);
+        }
+        let moving = self.game().take_entities(names)?;
+        let clashes = Vec::from_iter(
+            moving
+                .iter()
+                .map(|(entity, _)| entity.name())
+                .filter(|name| self.tabs[target].1.has_entity(name)),
+        );
+        if !clashes.is_empty() {
+            let error =
+                "Already in ".to_string() + &self.tabs[target].0 + ": " + &clashes.join(", ");
+            self.game().add_entities(moving);
+            return Err(error);
+        }
+        let moved = Vec::from_iter(moving.iter().map(|(entity, _)| entity.name().to_string()));
+        self.tabs[target].1.add_entities(moving);
+        Ok("Moved ".to_string() + &moved.join(", ") + " to " + &self.tabs[target].0)
+    }
+
+    // tab commands are handled here, anything else goes to the game in the tab being shown
+    pub fn process_command(&mut self, command: String) -> Result<String, String> {
+        let args = command.split_whitespace().collect::<Vec<&str>>();
+        match args.first().copied().unwrap_or("") {
+            "tabs" => Ok(self.list()),
+            "new_tab" => {
+                let name = match args.get(1) {
+                    Some(name) => name.to_string(),
+                    None => {
+                        let mut number = self.tabs.len() + 1;
+                        while self
+                            .check_name(&("Tab_".to_string() + &number.to_string()))
+                            .is_err()
+                        {
+                            number += 1;
+                        }
+                        "Tab_".to_string() + &number.to_string()
+                    }
+                };
+                self.check_name(&name)?;
+                let game = self.new_game();
+                self.tabs.push((name.clone(), game));
+                self.active = self.tabs.len() - 1;
+                Ok("Opened tab ".to_string() + &name)
+            }
+            "tab" => {
+                if args.len() < 2 {
+                    return Err("Not enough arguments".to_string());
+                }
+                self.active = self.find(args[1])?;
+                Ok("Switched to ".to_string() + &self.tabs[self.active].0)
+            }
+            "rename_tab" => {
+                if args.len() < 2 {
+                    return Err("Not enough arguments".to_string());
+                }
+                if !self.tabs[self.active].0.eq_ignore_ascii_case(args[1]) {
+                    self.check_name(args[1])?;
+                }
+                let old = std::mem::replace(&mut self.tabs[self.active].0, args[1].to_string());
+                Ok("Renamed ".to_string() + &old + " to " + args[1])
+            }
+            "close_tab" => {
+                let index = match args.get(1) {
+                    Some(name) => self.find(name)?,
+                    None => self.active,
+                };
+                if self.tabs.len() == 1 {
+                    return Err("Can't close the last tab".to_string());
+                }
+                let (name, mut game) = self.tabs.remove(index);
+                game.stop_servers();
+                if self.active > index || self.active == self.tabs.len() {
+                    self.active -= 1;
+                }
+                Ok("Closed ".to_string() + &name)
+            }
+            "move_to_tab" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                self.move_entities(args[1], &args[2..])
+            }
+            "save_tabs" => {
+                if args.len() < 2 {
+                    return Err("Not enough arguments".to_string());
+                }
+                self.save(args[1])
+            }
+            "load_tabs" => {
+                if args.len() < 2 {
+                    return Err("Not enough arguments".to_string());
+                }
+                self.load(args[1])
+            }
+            _ => self.game().process_command(command),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tabs(commands: &[&str]) -> Tabs {
+        let mut tabs = Tabs::default();
+        for command in commands {
+            tabs.process_command(command.to_string()).unwrap();
+        }
+        tabs
+    }
+
+    #[test]
+    fn tab_saves_round_trip() {
+        let mut saved = tabs(&[
+            "add_entity Aria party",
+            "damage 3 Aria",
+            "new_tab Cellar",
+            "add_entity Goblin enemy",
+            "tab Main",
+        ]);
+        let mut loaded = tabs(&["new_tab Other"]);
+        loaded.load_text(&saved.save_text()).unwrap();
+        assert_eq!(loaded.get_names(), ["Main", "Cellar"]);
+        assert_eq!(loaded.get_active(), 0);
+        for i in 0..2 {
+            saved.active = i;
+            loaded.active = i;
+            assert_eq!(
+                loaded.game().get_entities_list(),
+                saved.game().get_entities_list()
+            );
+        }
+        assert!(loaded.load_text("tab|Main|x\n").is_err());
+        assert!(loaded
+            .process_command("save_tabs ../x".to_string())
+            .is_err());
+        assert!(loaded
+            .process_command("load_tabs ../x".to_string())
+            .is_err());
+    }
+
+    #[test]
+    fn remote_commands_run_on_their_own_tab() {
+        let mut tabs = tabs(&[
+            "add_entity Aria party",
+            "new_tab Cellar",
+            "add_entity Aria party",
+        ]);
+        tabs.process_remote_command(0, "damage 4 Aria".to_string())
+            .unwrap();
+        assert!(tabs
+            .process_remote_command(0, "save all x".to_string())
+            .is_err());
+        assert_eq!(tabs.game().get_entities()[0].damage_taken(), 0);
+        tabs.active = 0;
+        assert_eq!(tabs.game().get_entities()[0].damage_taken(), 4);
+    }
+
+    #[test]
+    fn closing_a_tab_stops_its_api() {
+        let mut tabs = tabs(&["new_tab Cellar"]);
+        let started = tabs.process_command("api 0 secret".to_string()).unwrap();
+        let port = started["Command API on port ".len()..]
+            .split(',')
+            .next()
+            .and_then(|x| x.parse::<u16>().ok())
+            .unwrap();
+        tabs.process_command("close_tab Cellar".to_string())
+            .unwrap();
+        assert!(std::net::TcpListener::bind(("127.0.0.1", port)).is_ok());
+    }
+}
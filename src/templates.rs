@@ -0,0 +1,221 @@
+use crate::dice;
+use crate::difficulty;
+use crate::json;
+
+// stat blocks are read from every .json file in here, either a single monster or a list of them
+pub const TEMPLATE_DIRECTORY: &str = "monsters";
+
+// the parts of an SRD stat block the tracker uses
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub name: String,
+    pub max_hp: Option<u16>,
+    pub hit_points_roll: Option<dice::DiceExpression>,
+    pub armor_class: Option<u16>,
+    // ability abbreviation and bonus, like ("DEX", 4)
+    pub saves: Vec<(String, i16)>,
+    pub resistances: Vec<String>,
+    pub immunities: Vec<String>,
+    pub vulnerabilities: Vec<String>,
+    pub initiative_modifier: i16,
+    // xp the creature is worth, from its challenge rating when not given
+    pub xp: Option<u32>,
+    // creature level, for pathfinder stat blocks
+    pub level: Option<i16>,
+    // name, uses and the cadence they come back on
+    pub resources: Vec<(String, u16, String)>,
+}
+
+impl Template {
+    // names as typed in commands, "Adult Red Dragon" becomes Adult_Red_Dragon
+    pub fn key(&self) -> String {
+        self.name.replace([' ', '-'], "_")
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        normalize(&self.name) == normalize(name)
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.to_lowercase().replace([' ', '-'], "_")
+}
+
+const ABILITIES: [(&str, &str); 6] = [
+    ("strength", "STR"),
+    ("dexterity", "DEX"),
+    ("constitution", "CON"),
+    ("intelligence", "INT"),
+    ("wisdom", "WIS"),
+    ("charisma", "CHA"),
+];
+
+// lists are either arrays of strings or, in older exports, one string with
+// groups like "bludgeoning, piercing, and slashing from nonmagical attacks" split by ;
+fn string_list(value: Option<&json::Value>) -> Vec<String> {
+    match value {
+        Some(json::Value::String(list)) => list
+            .split(';')
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+            .collect(),
+        Some(value) => value
+            .as_array()
+            .iter()
+            .filter_map(|x| x.as_str().map(|x| x.to_string()))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+// "Legendary Resistance (3/Day)" becomes Legendary_Resistance so it can be used in commands
+fn resource_name(name: &str) -> String {
+    let name = match name.find('(') {
+        Some(index) => &name[..index],
+        None => name,
+    };
+    name.trim().replace(' ', "_")
+}
+
+fn parse_template(value: &json::Value) -> Result<Template, String> {
+    let name = value
+        .get("name")
+        .and_then(|x| x.as_str())
+        .ok_or("Stat block without a name".to_string())?
+        .to_string();
+    let armor_class = match value.get("armor_class") {
+        Some(json::Value::Number(armor_class)) => Some(*armor_class as u16),
+        Some(json::Value::Array(values)) => values
+            .first()
+            .and_then(|x| x.get("value"))
+            .and_then(|x| x.as_f64())
+            .map(|x| x as u16),
+        _ => None,
+    };
+    let hit_points_roll = value
+        .get("hit_points_roll")
+        .or_else(|| value.get("hit_dice"))
+        .and_then(|x| x.as_str())
+        .and_then(|x| dice::DiceExpression::parse(&x.replace(' ', "")).ok());
+    let dexterity = value
+        .get("dexterity")
+        .and_then(|x| x.as_f64())
+        .unwrap_or(10.0);
+    let mut saves = Vec::new();
+    for proficiency in value.get("proficiencies").map_or(&[][..], |x| x.as_array()) {
+        let name = proficiency
+            .get("proficiency")
+            .and_then(|x| x.get("name"))
+            .and_then(|x| x.as_str())
+            .unwrap_or("");
+        if let (Some(ability), Some(bonus)) = (
+            name.strip_prefix("Saving Throw: "),
+            proficiency.get("value").and_then(|x| x.as_f64()),
+        ) {
+            saves.push((ability.to_string(), bonus as i16));
+        }
+    }
+    for (ability, abbreviation) in ABILITIES {
+        if let Some(bonus) = value
+            .get(&(ability.to_string() + "_save"))
+            .and_then(|x| x.as_f64())
+        {
+            saves.push((abbreviation.to_string(), bonus as i16));
+        }
+    }
+    let mut resources = Vec::new();
+    for list in ["special_abilities", "actions", "reactions"] {
+        for ability in value.get(list).map_or(&[][..], |x| x.as_array()) {
+            let (Some(ability_name), Some(usage)) = (
+                ability.get("name").and_then(|x| x.as_str()),
+                ability.get("usage"),
+            ) else {
+                continue;
+            };
+            let times = usage.get("times").and_then(|x| x.as_f64()).unwrap_or(1.0) as u16;
+            match usage.get("type").and_then(|x| x.as_str()) {
+                Some("per day") => {
+                    resources.push((resource_name(ability_name), times, "long".to_string()))
+                }
+                Some("recharge after rest") => {
+                    resources.push((resource_name(ability_name), times, "short".to_string()))
+                }
+                _ => {}
+            }
+        }
+    }
+    if !value
+        .get("legendary_actions")
+        .map_or(&[][..], |x| x.as_array())
+        .is_empty()
+    {
+        resources.push(("Legendary_Actions".to_string(), 3, "turn".to_string()));
+    }
+    let challenge_rating = match value.get("challenge_rating").or_else(|| value.get("cr")) {
+        Some(json::Value::Number(cr)) => Some(cr.to_string()),
+        Some(json::Value::String(cr)) => Some(cr.clone()),
+        _ => None,
+    };
+    let xp = match value.get("xp").and_then(|x| x.as_f64()) {
+        Some(xp) => Some(xp as u32),
+        None => challenge_rating.and_then(|x| difficulty::cr_to_xp(&x)),
+    };
+    Ok(Template {
+        name,
+        xp,
+        level: value
+            .get("level")
+            .and_then(|x| x.as_f64())
+            .map(|x| x as i16),
+        max_hp: value
+            .get("hit_points")
+            .and_then(|x| x.as_f64())
+            .map(|x| x as u16),
+        hit_points_roll,
+        armor_class,
+        saves,
+        resistances: string_list(value.get("damage_resistances")),
+        immunities: string_list(value.get("damage_immunities")),
+        vulnerabilities: string_list(value.get("damage_vulnerabilities")),
+        initiative_modifier: ((dexterity as i16) - 10).div_euclid(2),
+        resources,
+    })
+}
+
+// reads every stat block file, files that fail to parse are reported in the errors
+pub fn load_templates() -> (Vec<Template>, Vec<String>) {
+    let mut templates = Vec::new();
+    let mut errors = Vec::new();
+    let Ok(files) = std::fs::read_dir(TEMPLATE_DIRECTORY) else {
+        return (templates, errors);
+    };
+    let mut paths = Vec::from_iter(files.filter_map(|x| x.ok()).map(|x| x.path()));
+    paths.sort();
+    for path in paths {
+        if path.extension().and_then(|x| x.to_str()) != Some("json") {
+            continue;
+        }
+        let file_name = path.display().to_string();
+        let value = match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|x| json::parse(&x))
+        {
+            Ok(value) => value,
+            Err(e) => {
+                errors.push(file_name + ": " + &e);
+                continue;
+            }
+        };
+        let blocks = match &value {
+            json::Value::Array(blocks) => blocks.clone(),
+            _ => vec![value],
+        };
+        for block in blocks.iter() {
+            match parse_template(block) {
+                Ok(template) => templates.push(template),
+                Err(e) => errors.push(file_name.clone() + ": " + &e),
+            }
+        }
+    }
+    (templates, errors)
+}
@@ -1,112 +1,123 @@
-#[derive(Debug, Eq, Copy, Clone)]
-pub enum Team {
-    Party,
-    Allies,
-    Neutral,
-    Enemy,
-    Unknown,
-}
+use crate::dice;
+use crate::difficulty;
+use crate::encounter;
+use crate::log;
+use crate::server;
+use crate::templates;
 
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
-enum DurationUnit {
-    Unknown,
-    Turns,
-    Minutes,
-    Hours,
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Faction {
+    // lowercase key entities refer to their faction by
+    id: String,
+    display_name: String,
+    abbreviations: Vec<String>,
+    color: [u8; 3],
+    // lower sorts first in the entity list
+    priority: i16,
 }
 
-impl DurationUnit {
-    fn to_string(&self) -> String {
-        match self {
-            DurationUnit::Unknown => "".to_string(),
-            DurationUnit::Turns => "Turns".to_string(),
-            DurationUnit::Minutes => "Minutes".to_string(),
-            DurationUnit::Hours => "Hours".to_string(),
+impl Faction {
+    fn new(id: &str, display_name: &str, color: [u8; 3], priority: i16) -> Faction {
+        Faction {
+            id: id.to_lowercase(),
+            display_name: display_name.to_string(),
+            abbreviations: Vec::new(),
+            color,
+            priority,
         }
     }
-}
 
-impl From<String> for Team {
-    fn from(team: String) -> Self {
-        match team.to_lowercase().as_str() {
-            "p" => Team::Party,
-            "a" => Team::Allies,
-            "n" => Team::Neutral,
-            "e" => Team::Enemy,
-            "party" => Team::Party,
-            "ally" => Team::Allies,
-            "allies" => Team::Allies,
-            "neutral" => Team::Neutral,
-            "enemy" => Team::Enemy,
-            _ => Team::Unknown,
-        }
+    fn with_abbreviations(mut self, abbreviations: &[&str]) -> Faction {
+        self.abbreviations = abbreviations.iter().map(|x| x.to_string()).collect();
+        self
     }
-}
 
-impl From<Team> for String {
-    fn from(team: Team) -> Self {
-        match team {
-            Team::Party => "party".to_string(),
-            Team::Allies => "ally".to_string(),
-            Team::Neutral => "neutral".to_string(),
-            Team::Enemy => "enemy".to_string(),
-            Team::Unknown => "unknown".to_string(),
-        }
+    // the built in teams, ordered party, allies, enemy, neutral, then anything unknown
+    fn defaults() -> Vec<Faction> {
+        vec![
+            Faction::new("party", "Party", [80, 200, 120], 0).with_abbreviations(&["p"]),
+            Faction::new("ally", "Allies", [90, 160, 230], 10).with_abbreviations(&["a", "allies"]),
+            Faction::new("enemy", "Enemy", [230, 80, 80], 20).with_abbreviations(&["e"]),
+            Faction::new("neutral", "Neutral", [200, 200, 120], 30).with_abbreviations(&["n"]),
+            Faction::new(UNKNOWN_FACTION, "Unknown Team", [160, 160, 160], i16::MAX),
+        ]
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.id == name
+            || self.display_name.to_lowercase() == name
+            || self.abbreviations.contains(&name)
+    }
+
+    pub fn color(&self) -> [u8; 3] {
+        self.color
     }
 }
 
-impl Team {
-    fn to_string(&self) -> String {
-        match self {
-            Team::Party => "Party".to_string(),
-            Team::Allies => "Allies".to_string(),
-            Team::Neutral => "Neutral".to_string(),
-            Team::Enemy => "Enemy".to_string(),
-            Team::Unknown => "Unknown Team".to_string(),
-        }
+impl std::fmt::Display for Faction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.display_name)
     }
 }
 
-impl Ord for Team {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        // ordering: 1st: party, 2nd : allies, 3rd: enemy, 4th: neutral
-        match self {
-            Team::Party => match other {
-                Team::Allies => std::cmp::Ordering::Less,
-                Team::Enemy => std::cmp::Ordering::Less,
-                Team::Neutral => std::cmp::Ordering::Less,
-                Team::Unknown => std::cmp::Ordering::Less,
-                _ => std::cmp::Ordering::Equal,
-            },
-            Team::Allies => match other {
-                Team::Neutral => std::cmp::Ordering::Less,
-                Team::Enemy => std::cmp::Ordering::Less,
-                Team::Party => std::cmp::Ordering::Greater,
-                Team::Unknown => std::cmp::Ordering::Less,
-                _ => std::cmp::Ordering::Equal,
-            },
-            Team::Enemy => match other {
-                Team::Neutral => std::cmp::Ordering::Less,
-                Team::Allies => std::cmp::Ordering::Greater,
-                Team::Party => std::cmp::Ordering::Greater,
-                Team::Unknown => std::cmp::Ordering::Less,
-                _ => std::cmp::Ordering::Equal,
-            },
-            Team::Unknown => std::cmp::Ordering::Greater,
-            Team::Neutral => std::cmp::Ordering::Greater,
-        }
+const UNKNOWN_FACTION: &str = "unknown";
+
+// most copies add_entity makes at once
+const MAX_COPIES: usize = 100;
+
+// accepts #rrggbb or a handful of color names
+fn parse_color(color: &str) -> Result<[u8; 3], String> {
+    match color.to_lowercase().as_str() {
+        "red" => return Ok([230, 80, 80]),
+        "green" => return Ok([80, 200, 120]),
+        "blue" => return Ok([90, 160, 230]),
+        "yellow" => return Ok([220, 200, 80]),
+        "orange" => return Ok([240, 150, 60]),
+        "purple" => return Ok([170, 110, 220]),
+        "grey" | "gray" => return Ok([160, 160, 160]),
+        _ => (),
     }
+    let hex = color.trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err("Invalid color, use #rrggbb or a color name".to_string());
+    }
+    let mut rgb = [0; 3];
+    for (i, channel) in rgb.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| "Invalid color, use #rrggbb or a color name".to_string())?;
+    }
+    Ok(rgb)
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+enum DurationUnit {
+    Unknown,
+    Turns,
+    Minutes,
+    Hours,
 }
 
-impl PartialEq for Team {
-    fn eq(&self, other: &Self) -> bool {
-        self.cmp(other) == std::cmp::Ordering::Equal
+impl std::fmt::Display for DurationUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DurationUnit::Unknown => write!(f, ""),
+            DurationUnit::Turns => write!(f, "Turns"),
+            DurationUnit::Minutes => write!(f, "Minutes"),
+            DurationUnit::Hours => write!(f, "Hours"),
+        }
     }
 }
 
-impl PartialOrd for Team {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+impl DurationUnit {
+    // how many seconds of game time one unit covers, a round being 6 seconds
+    fn seconds(&self) -> Option<u32> {
+        match self {
+            DurationUnit::Unknown => None,
+            DurationUnit::Turns => Some(6),
+            DurationUnit::Minutes => Some(60),
+            DurationUnit::Hours => Some(3600),
+        }
     }
 }
 
@@ -130,37 +141,641 @@ struct Duration {
     unit: DurationUnit,
 }
 
+impl Duration {
+    fn seconds(&self) -> Option<u32> {
+        self.unit.seconds().map(|x| x * self.length as u32)
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone)]
-struct StatusEffect {
+pub struct StatusEffect {
     name: String,
     duration: Duration,
+    // game clock time the effect was applied at
+    applied_at: u32,
+    // secret effects are left out of the player view
+    secret: bool,
+}
+
+impl StatusEffect {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn duration(&self) -> String {
+        self.duration.length.to_string() + " " + &self.duration.unit.to_string()
+    }
+
+    pub fn secret(&self) -> bool {
+        self.secret
+    }
+
+    // effects with an unknown unit never run out on their own
+    fn expired(&self, clock: u32) -> bool {
+        match self.duration.seconds() {
+            Some(seconds) => self.applied_at.saturating_add(seconds) <= clock,
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+enum ResetCadence {
+    Turn,
+    Round,
+    ShortRest,
+    LongRest,
+}
+
+impl ResetCadence {
+    // as written in commands and files
+    fn id(&self) -> &str {
+        match self {
+            ResetCadence::Turn => "turn",
+            ResetCadence::Round => "round",
+            ResetCadence::ShortRest => "short",
+            ResetCadence::LongRest => "long",
+        }
+    }
+}
+
+impl TryFrom<String> for ResetCadence {
+    type Error = String;
+
+    fn try_from(cadence: String) -> Result<Self, Self::Error> {
+        match cadence.to_lowercase().as_str() {
+            "turn" | "t" => Ok(ResetCadence::Turn),
+            "round" | "r" => Ok(ResetCadence::Round),
+            "short" | "s" => Ok(ResetCadence::ShortRest),
+            "long" | "l" => Ok(ResetCadence::LongRest),
+            _ => Err("Resources reset on turn, round, short or long, not ".to_string() + &cadence),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+struct Resource {
+    name: String,
+    current: u16,
+    max: u16,
+    reset: ResetCadence,
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+enum ActionKind {
+    Action,
+    BonusAction,
+    Reaction,
+    Movement,
+}
+
+impl TryFrom<String> for ActionKind {
+    type Error = String;
+
+    fn try_from(kind: String) -> Result<Self, Self::Error> {
+        match kind.to_lowercase().as_str() {
+            "action" | "a" => Ok(ActionKind::Action),
+            "bonus" | "b" => Ok(ActionKind::BonusAction),
+            "reaction" | "r" => Ok(ActionKind::Reaction),
+            "movement" | "move" | "m" => Ok(ActionKind::Movement),
+            _ => Err("Expected action, bonus, reaction or movement".to_string()),
+        }
+    }
+}
+
+// running totals kept for the current encounter and over an entity's lifetime
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+pub struct CombatStats {
+    pub damage_dealt: u32,
+    pub damage_taken: u32,
+    pub healing_done: u32,
+    pub kills: u16,
+    pub crits: u16,
+    pub rounds_survived: u16,
+    // seconds of game time each effect was active for
+    pub effect_uptime: Vec<(String, u32)>,
+}
+
+impl CombatStats {
+    fn add_uptime(&mut self, effect: &str, seconds: u32) {
+        match self
+            .effect_uptime
+            .iter_mut()
+            .find(|(name, _)| name == effect)
+        {
+            Some((_, total)) => *total = total.saturating_add(seconds),
+            None => self.effect_uptime.push((effect.to_string(), seconds)),
+        }
+    }
+}
+
+// rounds while in combat, minutes and hours for anything longer
+pub fn format_seconds(seconds: u32) -> String {
+    if seconds < 60 {
+        (seconds / 6).to_string() + " rounds"
+    } else if seconds < 3600 {
+        (seconds / 60).to_string() + " minutes"
+    } else {
+        (seconds / 3600).to_string() + " hours"
+    }
+}
+
+impl std::fmt::Display for CombatStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "dealt {}, taken {}, healed {}, kills {}, crits {}, rounds survived {}",
+            self.damage_dealt,
+            self.damage_taken,
+            self.healing_done,
+            self.kills,
+            self.crits,
+            self.rounds_survived
+        )?;
+        if !self.effect_uptime.is_empty() {
+            let uptime = Vec::from_iter(
+                self.effect_uptime
+                    .iter()
+                    .map(|(name, seconds)| name.clone() + " " + &format_seconds(*seconds)),
+            );
+            write!(f, ", effects: {}", uptime.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+// what an entity has already spent this turn
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+struct ActionEconomy {
+    action: bool,
+    bonus_action: bool,
+    reaction: bool,
+    movement: bool,
+}
+
+impl ActionEconomy {
+    fn spent(&mut self, kind: ActionKind) -> &mut bool {
+        match kind {
+            ActionKind::Action => &mut self.action,
+            ActionKind::BonusAction => &mut self.bonus_action,
+            ActionKind::Reaction => &mut self.reaction,
+            ActionKind::Movement => &mut self.movement,
+        }
+    }
+}
+
+impl std::fmt::Display for ActionEconomy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let icon = |spent: bool, icon: char| if spent { '-' } else { icon };
+        write!(
+            f,
+            "[{} {} {} {}]",
+            icon(self.action, 'A'),
+            icon(self.bonus_action, 'B'),
+            icon(self.reaction, 'R'),
+            icon(self.movement, 'M')
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Entity {
     name: String,
     damage_taken: u16,
-    team: Team,
+    max_hp: Option<u16>,
+    // id of the faction the entity belongs to
+    team: String,
     status_effects: Vec<StatusEffect>,
+    hit_dice: Option<dice::DiceExpression>,
+    hit_dice_remaining: u16,
+    exhaustion: u8,
+    resources: Vec<Resource>,
+    initiative: Option<i16>,
+    actions: ActionEconomy,
+    // reminder for lair actions, hazards and other initiative entries that aren't creatures
+    event: Option<String>,
+    // hidden entities are only shown to the GM
+    hidden: bool,
+    encounter_stats: CombatStats,
+    lifetime_stats: CombatStats,
+    // whoever dealt the damage that took the entity down
+    killed_by: Option<String>,
+    armor_class: Option<u16>,
+    saves: Vec<(String, i16)>,
+    resistances: Vec<String>,
+    immunities: Vec<String>,
+    vulnerabilities: Vec<String>,
+    initiative_modifier: i16,
+    // xp the creature is worth when defeated
+    xp: Option<u32>,
+    // character level for the party, creature level for pathfinder monsters
+    level: Option<i16>,
+    // xp earned, for party members
+    experience: u32,
+    // defeated and already counted towards the party's xp
+    xp_awarded: bool,
+    // markdown kept by the GM, like who's holding the idol
+    notes: String,
+    tags: Vec<String>,
 }
 
 impl Entity {
-    pub fn new(name: String, team: Team) -> Entity {
+    pub fn new(name: String, team: String) -> Entity {
         Entity {
             name: name.to_string(),
             damage_taken: 0,
-            team: team,
+            max_hp: None,
+            team,
             status_effects: Vec::new(),
+            hit_dice: None,
+            hit_dice_remaining: 0,
+            exhaustion: 0,
+            resources: Vec::new(),
+            initiative: None,
+            actions: ActionEconomy::default(),
+            event: None,
+            hidden: false,
+            encounter_stats: CombatStats::default(),
+            lifetime_stats: CombatStats::default(),
+            killed_by: None,
+            armor_class: None,
+            saves: Vec::new(),
+            resistances: Vec::new(),
+            immunities: Vec::new(),
+            vulnerabilities: Vec::new(),
+            initiative_modifier: 0,
+            xp: None,
+            level: None,
+            experience: 0,
+            xp_awarded: false,
+            notes: String::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn new_event(name: String, initiative: i16, reminder: String) -> Entity {
+        Entity {
+            initiative: Some(initiative),
+            event: Some(reminder),
+            ..Entity::new(name, UNKNOWN_FACTION.to_string())
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn damage_taken(&self) -> u16 {
+        self.damage_taken
+    }
+
+    pub fn max_hp(&self) -> Option<u16> {
+        self.max_hp
+    }
+
+    pub fn team(&self) -> &str {
+        &self.team
+    }
+
+    pub fn status_effects(&self) -> &[StatusEffect] {
+        &self.status_effects
+    }
+
+    pub fn initiative(&self) -> Option<i16> {
+        self.initiative
+    }
+
+    pub fn event(&self) -> Option<&str> {
+        self.event.as_deref()
+    }
+
+    pub fn hidden(&self) -> bool {
+        self.hidden
+    }
+
+    pub fn encounter_stats(&self) -> &CombatStats {
+        &self.encounter_stats
+    }
+
+    pub fn lifetime_stats(&self) -> &CombatStats {
+        &self.lifetime_stats
+    }
+
+    pub fn killed_by(&self) -> Option<&str> {
+        self.killed_by.as_deref()
+    }
+
+    pub fn notes(&self) -> &str {
+        &self.notes
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn apply_template(&mut self, template: &templates::Template) {
+        self.max_hp = template.max_hp;
+        self.armor_class = template.armor_class;
+        self.saves = template.saves.clone();
+        self.resistances = template.resistances.clone();
+        self.immunities = template.immunities.clone();
+        self.vulnerabilities = template.vulnerabilities.clone();
+        self.initiative_modifier = template.initiative_modifier;
+        self.xp = template.xp;
+        self.level = template.level;
+        // templates only ever write valid cadences
+        self.resources =
+            Vec::from_iter(template.resources.iter().filter_map(|(name, max, reset)| {
+                Some(Resource {
+                    name: name.clone(),
+                    current: *max,
+                    max: *max,
+                    reset: ResetCadence::try_from(reset.clone()).ok()?,
+                })
+            }));
+    }
+
+    // armor class, saves and damage types from a stat block, empty if there's none
+    pub fn stat_block(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(armor_class) = self.armor_class {
+            parts.push("AC: ".to_string() + &armor_class.to_string());
+        }
+        if !self.saves.is_empty() {
+            let saves = Vec::from_iter(
+                self.saves
+                    .iter()
+                    .map(|(ability, bonus)| format!("{} {:+}", ability, bonus)),
+            );
+            parts.push("Saves: ".to_string() + &saves.join(" "));
+        }
+        for (label, list) in [
+            ("Resistances: ", &self.resistances),
+            ("Immunities: ", &self.immunities),
+            ("Vulnerabilities: ", &self.vulnerabilities),
+        ] {
+            if !list.is_empty() {
+                parts.push(label.to_string() + &list.join(", "));
+            }
+        }
+        parts.join(", ")
+    }
+
+    // applies the same change to the encounter and lifetime totals
+    fn record(&mut self, change: impl Fn(&mut CombatStats)) {
+        change(&mut self.encounter_stats);
+        change(&mut self.lifetime_stats);
+    }
+
+    fn is_down(&self) -> bool {
+        self.max_hp
+            .is_some_and(|max_hp| self.damage_taken >= max_hp)
+    }
+
+    // party members see their own hit points, everyone else gets a descriptor
+    fn public_health(&self) -> String {
+        match self.max_hp {
+            Some(max_hp) if self.team == "party" => {
+                max_hp.saturating_sub(self.damage_taken).to_string()
+                    + "/"
+                    + &max_hp.to_string()
+                    + " HP"
+            }
+            _ => self.health_descriptor().to_string(),
+        }
+    }
+
+    // what the players get to see instead of exact hit points
+    fn health_descriptor(&self) -> &str {
+        match self.max_hp {
+            _ if self.damage_taken == 0 => "Unhurt",
+            Some(max_hp) if self.damage_taken >= max_hp => "Down",
+            // widened so large hit point totals can't overflow
+            Some(max_hp) if self.damage_taken as u32 * 4 >= max_hp as u32 * 3 => "Critical",
+            Some(max_hp) if self.damage_taken as u32 * 2 >= max_hp as u32 => "Bloodied",
+            _ => "Injured",
+        }
+    }
+
+    // compact "[A B R M]" row of what is left this turn
+    pub fn actions(&self) -> String {
+        self.actions.to_string()
+    }
+
+    pub fn resources(&self) -> String {
+        self.resources
+            .iter()
+            .map(|x| x.name.clone() + " " + &x.current.to_string() + "/" + &x.max.to_string())
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    // refills every resource that resets on any of the given cadences,
+    // returning the names of the ones that were actually spent
+    fn reset_resources(&mut self, cadences: &[ResetCadence]) -> Vec<String> {
+        let mut restored = Vec::new();
+        for resource in self.resources.iter_mut() {
+            if cadences.contains(&resource.reset) && resource.current < resource.max {
+                resource.current = resource.max;
+                restored.push(resource.name.clone());
+            }
         }
+        restored
     }
 }
 
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub struct Game {
     entities: Vec<Entity>,
+    factions: Vec<Faction>,
+    // seconds of game time passed, used to run out effect durations
+    clock: u32,
+    // kept so a session can be replayed with the same rolls
+    seed: u64,
+    rng: dice::Rng,
+    // name of the entity whose turn it is, None outside of combat
+    turn: Option<String>,
+    round: u16,
+    // names in the order set by move_turn and delay, overriding initiative
+    manual_order: Vec<String>,
+    server: Option<server::Server>,
+    api: Option<server::CommandApi>,
+    waker: Option<std::sync::Arc<dyn Fn() + Send + Sync>>,
+    // unmatched targets are an error instead of a warning
+    strict: bool,
+    // warnings from the command being run, added to its result
+    warnings: Vec<String>,
+    // every command run this session, for recaps
+    log: Vec<log::LogEntry>,
+    // who dealt the damage or healing of the command being run, for the log
+    last_source: Option<String>,
+    replay: Option<log::Replay>,
+    rules: difficulty::RuleSet,
+    // open campaign, its party is kept through clear and written back by end_combat
+    campaign: Option<String>,
+    // commands are being rerun for a replay, nothing gets written to the campaign
+    replaying: bool,
+    // the file the command being run read, it goes in the log and is handed back
+    // instead of reading the disk while replaying
+    file_read: Option<String>,
+    // stat blocks, read the first time they're needed
+    templates: Option<Vec<templates::Template>>,
+    // the last encounter started from the library
+    encounter: Option<encounter::Encounter>,
+    // entities removed during combat, the opponents among them still count for the xp
+    // when it ends
+    removed_in_combat: Vec<Entity>,
 }
 
-fn argumment_abreviations(arg: &str) -> &str {
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+enum RestLength {
+    Short,
+    Long,
+}
+
+impl RestLength {
+    fn seconds(&self) -> u32 {
+        match self {
+            RestLength::Short => 3600,
+            RestLength::Long => 8 * 3600,
+        }
+    }
+}
+
+// commands that change the game, only these are saved to and run from replays. load,
+// start and campaign keep the file they read in the log so they replay the same way,
+// stat blocks for add_entity are still read from the templates folder
+fn replayable(command: &str) -> bool {
+    matches!(
+        command,
+        "load"
+            | "start"
+            | "campaign"
+            | "add_entity"
+            | "add_event"
+            | "remove_entity"
+            | "add_effect"
+            | "remove_effect"
+            | "damage"
+            | "heal"
+            | "rest"
+            | "hit_dice"
+            | "exhaustion"
+            | "resource"
+            | "use"
+            | "restore"
+            | "hide"
+            | "reveal"
+            | "max_hp"
+            | "initiative"
+            | "next"
+            | "move_turn"
+            | "delay"
+            | "spend"
+            | "regain"
+            | "faction"
+            | "cr"
+            | "xp"
+            | "level"
+            | "rules"
+            | "end_combat"
+            | "strict"
+            | "clear"
+            | "note"
+            | "tag"
+            | "untag"
+    )
+}
+
+// commands other tools may run through the api, they only change the fight itself
+// and can't reach files, servers or the api
+pub fn check_remote_command(command: &str) -> Result<(), String> {
+    let first = argumment_abreviations(command.split_whitespace().next().unwrap_or(""));
+    if matches!(
+        first,
+        "damage"
+            | "heal"
+            | "add_effect"
+            | "remove_effect"
+            | "next"
+            | "initiative"
+            | "move_turn"
+            | "delay"
+            | "spend"
+            | "regain"
+            | "use"
+            | "restore"
+            | "exhaustion"
+            | "hide"
+            | "reveal"
+            | "stats"
+            | "factions"
+            | "help"
+    ) {
+        Ok(())
+    } else {
+        Err(first.to_string() + " can't be run through the api")
+    }
+}
+
+// hides the token in "api <port> <token>" so it doesn't end up in the log
+pub fn redact(command: &str) -> String {
+    let mut words = command.split_whitespace().collect::<Vec<&str>>();
+    if words.first().map(|x| argumment_abreviations(x)) != Some("api") {
+        return command.to_string();
+    }
+    if let Some(token) = words.iter_mut().filter(|x| !x.starts_with("--")).nth(2) {
+        *token = "<token>";
+    }
+    words.join(" ")
+}
+
+// case insensitive name match where * stands for any run of characters, like Goblin*
+fn name_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let name = name.to_lowercase();
+    let parts = pattern.split('*').collect::<Vec<&str>>();
+    if parts.len() == 1 {
+        return pattern == name;
+    }
+    let Some(mut rest) = name.strip_prefix(parts[0]) else {
+        return false;
+    };
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(parts[parts.len() - 1])
+}
+
+// number of single character edits between two names, for did you mean suggestions
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.to_lowercase().chars().collect::<Vec<char>>();
+    let b = b.to_lowercase().chars().collect::<Vec<char>>();
+    let mut previous = Vec::from_iter(0..=b.len());
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let substitution = previous[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            current[j] = substitution.min(previous[j] + 1).min(current[j - 1] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// suffix for the nth copy of a creature, 1 based, letters go A to Z
+fn copy_suffix(n: usize, letters: bool) -> String {
+    if letters {
+        ((b'A' + (n - 1) as u8) as char).to_string()
+    } else {
+        n.to_string()
+    }
+}
+
+pub fn argumment_abreviations(arg: &str) -> &str {
     match arg {
         "as" => "add_effect",
         "rs" => "remove_effect",
@@ -168,263 +783,3621 @@ fn argumment_abreviations(arg: &str) -> &str {
         "re" => "remove_entity",
         "d" => "damage",
         "h" => "heal",
+        "n" => "next",
         _ => arg,
     }
 }
 
-fn save(entities: Game, filename: String, team: Team) -> Result<String, String> {
-    let mut string = String::new();
-    for entity in entities.entities.iter() {
-        if entity.team == team || team == Team::Unknown {
-            string.push_str(&entity.name);
-            string.push_str("|");
-            string.push_str(&entity.team.to_string());
-            string.push_str("\n");
-        }
+// the options each command takes, anything else starting with -- is rejected
+pub fn command_flags(command: &str) -> &'static [&'static str] {
+    match command {
+        "add_entity" => &["--hidden", "--roll", "--letters"],
+        "add_effect" => &["--secret"],
+        "damage" => &["--crit"],
+        "stats" => &["--lifetime"],
+        "serve" => &["--lan"],
+        "api" => &["--lan"],
+        "clear" => &["--all"],
+        "note" => &["--replace", "--clear"],
+        "replay" => &["--force"],
+        _ => &[],
     }
-    //write to a file
-    match std::fs::write("saves/".to_string() + &filename + ".txt", string) {
-        Ok(_) => Ok("Saved ".to_string() + team.to_string().as_str() + " to " + filename.as_str()),
-        Err(e) => Err(e.to_string()),
+}
+
+// lifetime stats line for an entity, empty if it hasn't done anything yet
+fn stats_line(entity: &Entity) -> String {
+    let stats = &entity.lifetime_stats;
+    if *stats == CombatStats::default() {
+        return String::new();
+    }
+    let mut string = "stats|".to_string() + &entity.name;
+    for value in [
+        stats.damage_dealt,
+        stats.damage_taken,
+        stats.healing_done,
+        stats.kills as u32,
+        stats.crits as u32,
+        stats.rounds_survived as u32,
+    ] {
+        string.push('|');
+        string.push_str(&value.to_string());
     }
+    string.push('|');
+    let uptime = Vec::from_iter(
+        stats
+            .effect_uptime
+            .iter()
+            .map(|(name, seconds)| name.clone() + "=" + &seconds.to_string()),
+    );
+    string.push_str(&uptime.join(","));
+    string.push('\n');
+    string
 }
 
-fn load(filename: String) -> Result<Vec<Entity>, String> {
-    //load from file
-    match std::fs::read_to_string("saves/".to_string() + &filename + ".txt") {
-        Ok(contents) => {
-            let mut entities: Vec<Entity> = Vec::new();
-            for line in contents.lines() {
-                let line = line.split("|").collect::<Vec<&str>>();
-                if line.len() != 2 {
-                    return Err("Invalid save file".to_string());
-                }
-                entities.push(Entity::new(
-                    line[0].to_string(),
-                    Team::from(line[1].to_string()),
-                ));
-            }
-            Ok(entities)
-        }
-        Err(e) => Err(e.to_string()),
+fn parse_stats(entity: &mut Entity, line: &[&str]) -> Option<()> {
+    let stats = &mut entity.lifetime_stats;
+    stats.damage_dealt = line[2].parse().ok()?;
+    stats.damage_taken = line[3].parse().ok()?;
+    stats.healing_done = line[4].parse().ok()?;
+    stats.kills = line[5].parse().ok()?;
+    stats.crits = line[6].parse().ok()?;
+    stats.rounds_survived = line[7].parse().ok()?;
+    for uptime in line[8].split(',').filter(|x| !x.is_empty()) {
+        let (name, seconds) = uptime.split_once('=')?;
+        stats.add_uptime(name, seconds.parse().ok()?);
     }
+    Some(())
 }
 
-impl Game {
-    pub fn new() -> Game {
-        Game {
-            entities: Vec::new(),
-        }
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|x| x.to_string()).unwrap_or_default()
+}
+
+// hit points and the rest of what changes over a fight, campaigns and tab saves
+// write these after the name
+fn state_fields(entity: &Entity) -> String {
+    [
+        entity.damage_taken.to_string(),
+        optional(entity.max_hp),
+        optional(entity.hit_dice),
+        entity.hit_dice_remaining.to_string(),
+        entity.exhaustion.to_string(),
+        optional(entity.armor_class),
+        entity.initiative_modifier.to_string(),
+        optional(entity.level),
+        entity.experience.to_string(),
+    ]
+    .join("|")
+}
+
+const STATE_FIELDS: usize = 9;
+
+fn parse_state_fields(entity: &mut Entity, fields: &[&str]) -> Option<()> {
+    entity.damage_taken = fields[0].parse().ok()?;
+    entity.max_hp = fields[1].parse().ok();
+    entity.hit_dice = dice::DiceExpression::parse(fields[2]).ok();
+    entity.hit_dice_remaining = fields[3].parse().ok()?;
+    entity.exhaustion = fields[4].parse().ok()?;
+    entity.armor_class = fields[5].parse().ok();
+    entity.initiative_modifier = fields[6].parse().ok()?;
+    entity.level = fields[7].parse().ok();
+    entity.experience = fields[8].parse().ok()?;
+    Some(())
+}
+
+// lines following an entity, with their name and field count:
+// effect|<name>|<length>|<unit>|<applied at>|<secret>
+// resource|<name>|<current>|<max>|<cadence>
+// stats|<entity>|<dealt>|<taken>|<healing>|<kills>|<crits>|<rounds>|<effect>=<seconds>,...
+const DETAIL_LINES: [(&str, usize); 3] = [("effect", 6), ("resource", 5), ("stats", 9)];
+
+// notes|<entity>|<notes with new lines as \n>
+// tags|<entity>|<tag>,<tag>,...
+// saved with every kind of save, notes can contain | so they take the rest of the line
+fn notes_lines(entity: &Entity) -> String {
+    let mut string = String::new();
+    if !entity.notes.is_empty() {
+        string.push_str("notes|");
+        string.push_str(&entity.name);
+        string.push('|');
+        string.push_str(&escape_notes(&entity.notes));
+        string.push('\n');
     }
+    if !entity.tags.is_empty() {
+        string.push_str("tags|");
+        string.push_str(&entity.name);
+        string.push('|');
+        string.push_str(&entity.tags.join(","));
+        string.push('\n');
+    }
+    string
+}
 
-    pub fn get_entities_list(&mut self) -> String {
-        let mut list = String::new();
-        self.entities.sort_by(|a, b| a.team.cmp(&b.team));
-        let mut last_team = Team::Unknown;
-        for entity in self.entities.iter() {
-            if entity.team != last_team {
-                list.push_str("\n");
-                list.push_str(&entity.team.to_string());
-                list.push_str("\n");
-            }
-            last_team = entity.team;
-            list.push_str(&entity.name);
-            list.push_str(", Damage Taken: ");
-            list.push_str(&entity.damage_taken.to_string());
-            if entity.status_effects.len() > 0 {
-                list.push_str(", Status Effects: ");
+// notes are kept on one line in saves and commands, \ and new lines are escaped
+pub fn escape_notes(notes: &str) -> String {
+    notes.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+// the reverse of escape_notes, a \ before anything else is kept as typed
+fn unescape_notes(notes: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = notes.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('\\')) => {
+                unescaped.push('\\');
+                chars.next();
             }
-            for effect in entity.status_effects.iter() {
-                list.push_str(&effect.name);
-                list.push_str(", ");
-                list.push_str(&effect.duration.length.to_string());
-                list.push_str(" ");
-                list.push_str(&effect.duration.unit.to_string());
-                list.push_str("; ");
+            ('\\', Some('n')) => {
+                unescaped.push('\n');
+                chars.next();
             }
-            list.push_str("\n");
+            (c, _) => unescaped.push(c),
         }
-        list
     }
+    unescaped
+}
 
-    pub fn get_matchable_names(&mut self) -> Vec<String> {
-        let mut matchables = Vec::new();
-        for entity in self.entities.iter() {
-            matchables.push(entity.name.clone());
+fn is_notes_line(line: &[&str]) -> bool {
+    (line[0] == "notes" && line.len() >= 3) || (line[0] == "tags" && line.len() == 3)
+}
+
+// creatures the party fights that are worth xp under the rules in use
+fn is_opponent(entity: &Entity, rules: difficulty::RuleSet) -> bool {
+    entity.event.is_none()
+        && entity.team != "party"
+        && entity.team != "ally"
+        && match rules {
+            difficulty::RuleSet::Dnd5e => entity.xp.is_some(),
+            difficulty::RuleSet::Pathfinder2e => entity.level.is_some(),
         }
-        matchables
+}
+
+// the first field of every other kind of save line, an entity named one of these
+// would be read back as that line
+const SAVE_KEYWORDS: [&str; 12] = [
+    "event", "progress", "stats", "faction", "order", "state", "effect", "resource", "combat",
+    "notes", "tags", "settings",
+];
+
+// entity and faction names are written as fields of save lines
+fn check_save_name(name: &str) -> Result<(), String> {
+    if name.contains('|') || SAVE_KEYWORDS.contains(&name.to_lowercase().as_str()) {
+        return Err("Names can't contain | or be one of ".to_string() + &SAVE_KEYWORDS.join(", "));
     }
+    Ok(())
+}
 
-    pub fn process_command(&mut self, command: String) -> Result<String, String> {
-        let args: Vec<&str> = command.split(" ").collect();
-        match argumment_abreviations(args[0]) {
-            "add_effect" => {
-                if args.len() < 4 {
-                    return Err("Not enough arguments".to_string());
-                }
-                let entity_names =
-                    Vec::from_iter(args[4..].iter().map(|x| x.to_string().to_lowercase()));
-                let effect = args[1].to_string();
-                let duration = args[2].parse().unwrap_or_else(|_| 0);
-                let duration_unit = DurationUnit::from(args[3].to_string());
-                for entity in self.entities.iter_mut() {
-                    if entity_names.contains(&entity.name.to_lowercase()) {
-                        entity.status_effects.push(StatusEffect {
-                            name: effect.clone(),
-                            duration: Duration {
-                                length: duration,
-                                unit: duration_unit,
-                            },
-                        });
-                    }
-                }
-                Ok("Added effects".to_string())
-            }
-            "remove_effect" => {
-                if args.len() < 3 {
-                    return Err("Not enough arguments".to_string());
+// names that would be read as a selector or a wildcard couldn't be targeted
+fn check_entity_name(name: &str) -> Result<(), String> {
+    if name.starts_with('@') || name.contains('*') {
+        return Err("Names can't start with @ or contain *".to_string());
+    }
+    check_save_name(name)
+}
+
+fn parse_notes(entity: &mut Entity, line: &[&str]) -> Option<()> {
+    if entity.name != line[1] {
+        return None;
+    }
+    if line[0] == "notes" {
+        entity.notes = unescape_notes(&line[2..].join("|"));
+    } else {
+        entity.tags = Vec::from_iter(line[2].split(',').map(|x| x.to_string()));
+    }
+    Some(())
+}
+
+fn detail_lines(entity: &Entity) -> String {
+    let mut string = String::new();
+    for effect in entity.status_effects.iter() {
+        string.push_str("effect|");
+        string.push_str(&effect.name);
+        string.push('|');
+        string.push_str(&effect.duration.length.to_string());
+        string.push('|');
+        string.push_str(&effect.duration.unit.to_string().to_lowercase());
+        string.push('|');
+        string.push_str(&effect.applied_at.to_string());
+        string.push('|');
+        string.push_str(&effect.secret.to_string());
+        string.push('\n');
+    }
+    for resource in entity.resources.iter() {
+        string.push_str("resource|");
+        string.push_str(&resource.name);
+        string.push('|');
+        string.push_str(&resource.current.to_string());
+        string.push('|');
+        string.push_str(&resource.max.to_string());
+        string.push('|');
+        string.push_str(resource.reset.id());
+        string.push('\n');
+    }
+    string.push_str(&stats_line(entity));
+    string.push_str(&notes_lines(entity));
+    string
+}
+
+fn parse_detail(entity: &mut Entity, line: &[&str]) -> Option<()> {
+    match line[0] {
+        "effect" => entity.status_effects.push(StatusEffect {
+            name: line[1].to_string(),
+            duration: Duration {
+                length: line[2].parse().ok()?,
+                unit: DurationUnit::from(line[3].to_string()),
+            },
+            applied_at: line[4].parse().ok()?,
+            secret: line[5] == "true",
+        }),
+        "resource" => entity.resources.push(Resource {
+            name: line[1].to_string(),
+            current: line[2].parse().ok()?,
+            max: line[3].parse().ok()?,
+            reset: ResetCadence::try_from(line[4].to_string()).ok()?,
+        }),
+        _ if is_notes_line(line) => parse_notes(entity, line)?,
+        _ if entity.name == line[1] => parse_stats(entity, line)?,
+        _ => return None,
+    }
+    Some(())
+}
+
+// team of None saves every entity, a detailed save also keeps hit points, effects,
+// resources and where combat is at
+fn save_text(entities: &Game, team: &Option<String>, detailed: bool) -> String {
+    let mut string = String::new();
+    let defaults = Faction::defaults();
+    for faction in entities.factions.iter() {
+        if !defaults.contains(faction) {
+            string.push_str("faction|");
+            string.push_str(&faction.id);
+            string.push('|');
+            string.push_str(&faction.display_name);
+            string.push('|');
+            string.push_str(&faction.color.map(|x| format!("{:02x}", x)).concat());
+            string.push('|');
+            string.push_str(&faction.priority.to_string());
+            string.push('\n');
+        }
+    }
+    let order = entities.turn_order();
+    for entity in order.iter().map(|&i| &entities.entities[i]) {
+        if let (Some(reminder), Some(initiative), None) = (&entity.event, entity.initiative, &team)
+        {
+            string.push_str("event|");
+            string.push_str(&entity.name);
+            string.push('|');
+            string.push_str(&initiative.to_string());
+            string.push('|');
+            string.push_str(reminder);
+            string.push('\n');
+        } else if entity.event.is_none() && (team.is_none() || team.as_ref() == Some(&entity.team))
+        {
+            string.push_str(&entity.name);
+            string.push('|');
+            string.push_str(&entity.team);
+            if let Some(initiative) = entity.initiative {
+                string.push('|');
+                string.push_str(&initiative.to_string());
+            }
+            string.push('\n');
+            if entity.level.is_some() || entity.experience > 0 {
+                string.push_str("progress|");
+                string.push_str(&entity.name);
+                string.push('|');
+                string.push_str(&entity.level.map(|x| x.to_string()).unwrap_or_default());
+                string.push('|');
+                string.push_str(&entity.experience.to_string());
+                string.push('\n');
+            }
+            if detailed {
+                string.push_str("state|");
+                string.push_str(&entity.name);
+                string.push('|');
+                string.push_str(&state_fields(entity));
+                string.push('|');
+                string.push_str(&optional(entity.xp));
+                string.push('|');
+                string.push_str(&entity.hidden.to_string());
+                string.push('\n');
+                string.push_str(&detail_lines(entity));
+            } else {
+                string.push_str(&stats_line(entity));
+                string.push_str(&notes_lines(entity));
+            }
+        }
+    }
+    if !entities.manual_order.is_empty() {
+        string.push_str("order");
+        for name in order.iter().map(|&i| &entities.entities[i].name) {
+            string.push('|');
+            string.push_str(name);
+        }
+        string.push('\n');
+    }
+    if detailed {
+        string.push_str("combat|");
+        string.push_str(&entities.clock.to_string());
+        string.push('|');
+        string.push_str(&entities.round.to_string());
+        string.push('|');
+        string.push_str(entities.turn.as_deref().unwrap_or(""));
+        string.push('\n');
+    }
+    string
+}
+
+fn save(entities: &Game, filename: String, team: Option<String>) -> Result<String, String> {
+    let string = save_text(entities, &team, false);
+    let saved = match &team {
+        Some(team) => entities.faction(team).to_string(),
+        None => "all".to_string(),
+    };
+    //write to a file
+    match std::fs::write("saves/".to_string() + &filename + ".txt", string) {
+        Ok(_) => Ok("Saved ".to_string() + saved.as_str() + " to " + filename.as_str()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+struct SaveFile {
+    factions: Vec<Faction>,
+    entities: Vec<Entity>,
+    // turn order changed by hand, empty if it follows initiative
+    order: Vec<String>,
+    // clock, round and whose turn it is, only in detailed saves
+    combat: Option<(u32, u16, Option<String>)>,
+}
+
+fn parse_save(contents: &str) -> Result<SaveFile, String> {
+    let mut factions: Vec<Faction> = Vec::new();
+    let mut entities: Vec<Entity> = Vec::new();
+    let mut order: Vec<String> = Vec::new();
+    let mut combat = None;
+    for line in contents.lines() {
+        let line = line.split("|").collect::<Vec<&str>>();
+        if line[0] == "order" {
+            order = line[1..].iter().map(|x| x.to_string()).collect();
+            continue;
+        }
+        if line.len() == 4 && line[0] == "combat" {
+            let invalid = || "Invalid save file".to_string();
+            combat = Some((
+                line[1].parse().map_err(|_| invalid())?,
+                line[2].parse().map_err(|_| invalid())?,
+                Some(line[3].to_string()).filter(|x| !x.is_empty()),
+            ));
+            continue;
+        }
+        // everything about the entity on the line before, from detailed saves
+        if line.len() == STATE_FIELDS + 4 && line[0] == "state" {
+            let invalid = || "Invalid save file".to_string();
+            let entity = match entities.last_mut() {
+                Some(entity) if entity.name == line[1] => entity,
+                _ => return Err(invalid()),
+            };
+            parse_state_fields(entity, &line[2..]).ok_or_else(invalid)?;
+            entity.xp = line[STATE_FIELDS + 2].parse().ok();
+            entity.hidden = line[STATE_FIELDS + 3] == "true";
+            continue;
+        }
+        if line.len() == 4 && line[0] == "event" {
+            entities.push(Entity::new_event(
+                line[1].to_string(),
+                line[2]
+                    .parse()
+                    .map_err(|_| "Invalid save file".to_string())?,
+                line[3].to_string(),
+            ));
+            continue;
+        }
+        // level and xp earned for the entity on the line before
+        if line.len() == 4 && line[0] == "progress" {
+            let invalid = || "Invalid save file".to_string();
+            let entity = match entities.last_mut() {
+                Some(entity) if entity.name == line[1] => entity,
+                _ => return Err(invalid()),
+            };
+            if !line[2].is_empty() {
+                entity.level = Some(line[2].parse().map_err(|_| invalid())?);
+            }
+            entity.experience = line[3].parse().map_err(|_| invalid())?;
+            continue;
+        }
+        // lifetime stats, effects, resources and notes for the entity on the line before
+        if DETAIL_LINES.contains(&(line[0], line.len())) || is_notes_line(&line) {
+            entities
+                .last_mut()
+                .and_then(|entity| parse_detail(entity, &line))
+                .ok_or("Invalid save file".to_string())?;
+            continue;
+        }
+        if line.len() == 5 && line[0] == "faction" {
+            factions.push(Faction::new(
+                line[1],
+                line[2],
+                parse_color(line[3])?,
+                line[4]
+                    .parse()
+                    .map_err(|_| "Invalid save file".to_string())?,
+            ));
+            continue;
+        }
+        if line.len() != 2 && line.len() != 3 {
+            return Err("Invalid save file".to_string());
+        }
+        let mut entity = Entity::new(line[0].to_string(), line[1].to_string());
+        if line.len() == 3 {
+            entity.initiative = Some(
+                line[2]
+                    .parse()
+                    .map_err(|_| "Invalid save file".to_string())?,
+            );
+        }
+        entities.push(entity);
+    }
+    Ok(SaveFile {
+        factions,
+        entities,
+        order,
+        combat,
+    })
+}
+
+// names of the .txt files in a directory, sorted
+fn text_file_names(directory: &str) -> Vec<String> {
+    let Ok(files) = std::fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    let mut names = Vec::from_iter(files.filter_map(|x| x.ok()).filter_map(|x| {
+        x.file_name()
+            .to_str()
+            .and_then(|x| x.strip_suffix(".txt"))
+            .map(|x| x.to_string())
+    }));
+    names.sort();
+    names
+}
+
+// names typed for files inside one of the folders, they can't lead out of it
+pub fn check_file_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        return Err("File names can't be empty or contain /, \\ or ..".to_string());
+    }
+    Ok(())
+}
+
+pub const CAMPAIGN_DIRECTORY: &str = "campaigns";
+// every tab saved together, see tabs.rs
+pub const TAB_DIRECTORY: &str = "tabs";
+
+// the party characters kept between encounters and sessions
+struct Campaign {
+    // game time, so effects keep running out across sessions
+    clock: u32,
+    characters: Vec<Entity>,
+}
+
+// every party member with the state that carries over, one | separated line each:
+// clock|<seconds>
+// character|<name>|<damage taken>|<max hp>|<hit dice>|<hit dice left>|<exhaustion>|<armor class>|<initiative modifier>|<level>|<experience>
+// followed by its effect, resource and stats lines
+fn save_campaign(game: &Game, name: &str) -> Result<(), String> {
+    let path = CAMPAIGN_DIRECTORY.to_string() + "/" + name + ".txt";
+    // party members that aren't in this game, e.g. ones in another tab, stay in the file
+    let saved = match std::fs::read_to_string(&path) {
+        Ok(contents) => parse_campaign(&contents)?,
+        Err(_) => Campaign {
+            clock: 0,
+            characters: Vec::new(),
+        },
+    };
+    std::fs::create_dir_all(CAMPAIGN_DIRECTORY).map_err(|e| e.to_string())?;
+    std::fs::write(path, campaign_text(game, saved)).map_err(|e| e.to_string())
+}
+
+// the game's party members replace their saved copies, the rest of the saved ones are kept
+fn campaign_text(game: &Game, saved: Campaign) -> String {
+    let clock = game.clock.max(saved.clock);
+    let mut string = "clock|".to_string() + &clock.to_string() + "\n";
+    let kept = saved
+        .characters
+        .iter()
+        .filter(|x| !game.party_members().any(|member| member.name == x.name));
+    for entity in game.party_members().chain(kept) {
+        string.push_str("character|");
+        string.push_str(&entity.name);
+        string.push('|');
+        string.push_str(&state_fields(entity));
+        string.push('\n');
+        string.push_str(&detail_lines(entity));
+    }
+    string
+}
+
+fn parse_campaign(contents: &str) -> Result<Campaign, String> {
+    let invalid = || "Invalid campaign file".to_string();
+    let mut campaign = Campaign {
+        clock: 0,
+        characters: Vec::new(),
+    };
+    for line in contents.lines().filter(|x| !x.trim().is_empty()) {
+        let line = line.split('|').collect::<Vec<&str>>();
+        match (line[0], line.len()) {
+            ("clock", 2) => campaign.clock = line[1].parse().map_err(|_| invalid())?,
+            ("character", 11) => {
+                let mut entity = Entity::new(line[1].to_string(), "party".to_string());
+                parse_state_fields(&mut entity, &line[2..]).ok_or_else(invalid)?;
+                campaign.characters.push(entity);
+            }
+            detail if DETAIL_LINES.contains(&detail) || is_notes_line(&line) => {
+                campaign
+                    .characters
+                    .last_mut()
+                    .and_then(|entity| parse_detail(entity, &line))
+                    .ok_or_else(invalid)?;
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(campaign)
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+impl Game {
+    pub fn new() -> Game {
+        Game::with_seed(dice::Rng::default().next_u64())
+    }
+
+    pub fn with_seed(seed: u64) -> Game {
+        Game {
+            entities: Vec::new(),
+            factions: Faction::defaults(),
+            clock: 0,
+            seed,
+            rng: dice::Rng::new(seed),
+            turn: None,
+            round: 0,
+            manual_order: Vec::new(),
+            server: None,
+            api: None,
+            waker: None,
+            strict: false,
+            warnings: Vec::new(),
+            log: Vec::new(),
+            last_source: None,
+            replay: None,
+            rules: difficulty::RuleSet::default(),
+            campaign: None,
+            replaying: false,
+            file_read: None,
+            templates: None,
+            encounter: None,
+            removed_in_combat: Vec::new(),
+        }
+    }
+
+    fn party_members(&self) -> impl Iterator<Item = &Entity> {
+        self.entities
+            .iter()
+            .filter(|x| x.event.is_none() && x.team == "party")
+    }
+
+    // creatures the party is up against, anything not in the party or allied with it
+    // that's worth something under the current rules
+    fn opponents(&self) -> impl Iterator<Item = &Entity> {
+        let rules = self.rules;
+        self.entities.iter().filter(move |x| is_opponent(x, rules))
+    }
+
+    // difficulty of the creatures still standing against the party, None without
+    // party levels or opponents
+    pub fn get_difficulty(&self) -> Option<String> {
+        let party_levels = Vec::from_iter(self.party_members().filter_map(|x| x.level));
+        let standing = Vec::from_iter(self.opponents().filter(|x| !x.is_down()));
+        if party_levels.is_empty() || standing.is_empty() {
+            return None;
+        }
+        Some(match self.rules {
+            difficulty::RuleSet::Dnd5e => difficulty::dnd5e(
+                &party_levels,
+                &Vec::from_iter(standing.iter().filter_map(|x| x.xp)),
+            ),
+            difficulty::RuleSet::Pathfinder2e => difficulty::pf2e(
+                &party_levels,
+                &Vec::from_iter(standing.iter().filter_map(|x| x.level)),
+            ),
+        })
+    }
+
+    // awards xp for every defeated opponent to the party members still up and leaves combat
+    fn end_combat(&mut self) -> String {
+        let survivors = Vec::from_iter(
+            (0..self.entities.len())
+                .filter(|&i| self.entities[i].event.is_none() && self.entities[i].team == "party")
+                .filter(|&i| !self.entities[i].is_down()),
+        );
+        let party_level = difficulty::pf2e_party_level(&Vec::from_iter(
+            self.party_members().filter_map(|x| x.level),
+        ));
+        // the ones removed mid fight count as defeated too
+        let rules = self.rules;
+        let defeated = Vec::from_iter(
+            self.opponents()
+                .filter(|x| x.is_down())
+                .chain(
+                    self.removed_in_combat
+                        .iter()
+                        .filter(|x| is_opponent(x, rules)),
+                )
+                .filter(|x| !x.xp_awarded)
+                .map(|x| (x.name.clone(), x.xp, x.level)),
+        );
+        // 5e splits the xp between the party, in pathfinder everyone gets all of it
+        let (total, share) = match self.rules {
+            difficulty::RuleSet::Dnd5e => {
+                let total: u32 = defeated.iter().filter_map(|x| x.1).sum();
+                (total, total / survivors.len().max(1) as u32)
+            }
+            difficulty::RuleSet::Pathfinder2e => {
+                let total: u32 = defeated
+                    .iter()
+                    .filter_map(|x| x.2)
+                    .map(|x| difficulty::pf2e_creature_xp(x, party_level))
+                    .sum();
+                (total, total)
+            }
+        };
+        let mut result = "Combat ended in round ".to_string() + &self.round.to_string();
+        if survivors.is_empty() {
+            result.push_str(", no party members left to award XP to");
+        } else {
+            result.push_str(&format!(
+                ", {} XP from {} defeated, {} each:",
+                total,
+                defeated.len(),
+                share
+            ));
+            for entity in self.entities.iter_mut() {
+                if defeated.iter().any(|x| x.0 == entity.name) {
+                    entity.xp_awarded = true;
+                }
+            }
+            self.removed_in_combat.clear();
+            for i in survivors {
+                let entity = &mut self.entities[i];
+                entity.experience += share;
+                result.push('\n');
+                result.push_str(&entity.name);
+                result.push_str(": ");
+                result.push_str(&entity.experience.to_string());
+                result.push_str(" XP");
+            }
+        }
+        self.turn = None;
+        self.round = 0;
+        self.manual_order.clear();
+        self.encounter = None;
+        for entity in self.entities.iter_mut() {
+            if entity.event.is_none() {
+                entity.initiative = None;
+            }
+            entity.actions = ActionEconomy::default();
+        }
+        if let Some(campaign) = self.campaign.clone().filter(|_| !self.replaying) {
+            match save_campaign(self, &campaign) {
+                Ok(_) => result.push_str(&("\nSaved the party to ".to_string() + &campaign)),
+                Err(e) => self.warnings.push("Party not saved, ".to_string() + &e),
+            }
+        }
+        result
+    }
+
+    // adds what was saved to the game, combat picks up where it was if none is going on
+    fn apply_save(&mut self, save_file: SaveFile) {
+        for faction in save_file.factions {
+            if self.find_faction(&faction.id).is_none() {
+                self.factions.push(faction);
+            }
+        }
+        if !save_file.order.is_empty() {
+            let mut order = Vec::from_iter(
+                self.turn_order()
+                    .iter()
+                    .map(|&i| self.entities[i].name.clone()),
+            );
+            order.extend(save_file.order);
+            self.manual_order = order;
+        }
+        for mut entity in save_file.entities {
+            entity.team = self.faction_id(&entity.team);
+            self.entities.push(entity);
+        }
+        if let (Some((clock, round, turn)), None) = (save_file.combat, &self.turn) {
+            self.clock = self.clock.max(clock);
+            self.round = round;
+            self.turn = turn;
+        }
+    }
+
+    // everything in the game, for keeping several games in one file, starting with
+    // settings|<rules>|<strict>|<campaign>
+    pub fn to_save_text(&self) -> String {
+        let mut string = "settings|".to_string()
+            + &self.rules.to_string()
+            + "|"
+            + &self.strict.to_string()
+            + "|"
+            + self.campaign.as_deref().unwrap_or("")
+            + "\n";
+        string.push_str(&save_text(self, &None, true));
+        string
+    }
+
+    // older tab saves have no settings line
+    pub fn load_save_text(&mut self, contents: &str) -> Result<(), String> {
+        let (first, rest) = contents.split_once('\n').unwrap_or((contents, ""));
+        let Some(settings) = first.strip_prefix("settings|") else {
+            self.apply_save(parse_save(contents)?);
+            return Ok(());
+        };
+        let invalid = || "Invalid save file".to_string();
+        let settings = Vec::from_iter(settings.splitn(3, '|'));
+        if settings.len() != 3 {
+            return Err(invalid());
+        }
+        let rules = difficulty::RuleSet::try_from(settings[0].to_string())?;
+        let strict = settings[1].parse::<bool>().map_err(|_| invalid())?;
+        self.apply_save(parse_save(rest)?);
+        self.rules = rules;
+        self.strict = strict;
+        self.campaign = Some(settings[2].to_string()).filter(|x| !x.is_empty());
+        Ok(())
+    }
+
+    pub fn has_entity(&self, name: &str) -> bool {
+        self.entities
+            .iter()
+            .any(|x| x.name.eq_ignore_ascii_case(name))
+    }
+
+    // takes entities out to move them to another game, with the faction each belongs to
+    pub fn take_entities(&mut self, names: &[&str]) -> Result<Vec<(Entity, Faction)>, String> {
+        let mut targets = self.targets(names)?;
+        if let Some(&i) = targets
+            .iter()
+            .find(|&&i| self.turn.as_ref() == Some(&self.entities[i].name))
+        {
+            return Err("Can't move ".to_string() + &self.entities[i].name + " during its turn");
+        }
+        targets.sort();
+        let mut taken = Vec::new();
+        for i in targets.into_iter().rev() {
+            let entity = self.entities.remove(i);
+            self.manual_order.retain(|x| x != &entity.name);
+            let faction = self.faction(&entity.team).clone();
+            taken.insert(0, (entity, faction));
+        }
+        Ok(taken)
+    }
+
+    pub fn add_entities(&mut self, entities: Vec<(Entity, Faction)>) {
+        for (entity, faction) in entities {
+            if self.find_faction(&faction.id).is_none() {
+                self.factions.push(faction);
+            }
+            self.entities.push(entity);
+        }
+    }
+
+    fn party_names(&self) -> String {
+        let names = Vec::from_iter(self.party_members().map(|x| x.name.as_str()));
+        if names.is_empty() {
+            return "an empty party".to_string();
+        }
+        names.join(", ")
+    }
+
+    pub fn get_campaign(&self) -> Option<&str> {
+        self.campaign.as_deref()
+    }
+
+    pub fn get_encounter(&self) -> Option<&encounter::Encounter> {
+        self.encounter.as_ref()
+    }
+
+    // save, encounter and campaign files, replays get the copy kept in the log
+    fn read_file(&mut self, path: &str) -> std::io::Result<String> {
+        if self.replaying {
+            return self
+                .file_read
+                .clone()
+                .ok_or(std::io::ErrorKind::NotFound.into());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        self.file_read = Some(contents.clone());
+        Ok(contents)
+    }
+
+    // entities come back with the faction name as written in the file,
+    // older saves use display names like "Party" instead of ids
+    fn load(&mut self, filename: &str) -> Result<SaveFile, String> {
+        let contents = self
+            .read_file(&("saves/".to_string() + filename + ".txt"))
+            .map_err(|e| e.to_string())?;
+        parse_save(&contents)
+    }
+
+    // None if there's no campaign with that name yet
+    fn load_campaign(&mut self, name: &str) -> Result<Option<Campaign>, String> {
+        let path = CAMPAIGN_DIRECTORY.to_string() + "/" + name + ".txt";
+        let Ok(contents) = self.read_file(&path) else {
+            return Ok(None);
+        };
+        parse_campaign(&contents).map(Some)
+    }
+
+    fn templates(&mut self) -> &[templates::Template] {
+        self.templates
+            .get_or_insert_with(|| templates::load_templates().0)
+    }
+
+    // position and length of the replay being stepped through
+    pub fn get_replay(&self) -> Option<(usize, usize)> {
+        self.replay
+            .as_ref()
+            .map(|replay| (replay.position, replay.commands.len()))
+    }
+
+    // rebuilds the game from the seed by running the first steps of the replay,
+    // anything outside the game itself like the servers is carried over
+    fn replay_to(&mut self, step: usize) -> Result<String, String> {
+        let mut replay = match self.replay.take() {
+            Some(replay) => replay,
+            None => return Err("No replay loaded".to_string()),
+        };
+        let length = replay.commands.len();
+        let mut step = step.min(length);
+        let mut game = Game::with_seed(replay.seed);
+        game.replaying = true;
+        // only commands that worked are saved, one failing now means the replay went
+        // another way so it stops before it
+        let mut failed = None;
+        for (i, (time, command, file)) in replay.commands[..step].iter().enumerate() {
+            game.file_read = file.clone();
+            if let Err(e) = game.process_command(command.clone()) {
+                failed = Some(command.clone() + ": " + &e);
+                game.log.pop();
+                step = i;
+                break;
+            }
+            if let Some(entry) = game.log.last_mut() {
+                entry.time = *time;
+            }
+        }
+        game.replaying = false;
+        game.server = self.server.take();
+        game.api = self.api.take();
+        game.waker = self.waker.take();
+        replay.position = step;
+        game.replay = Some(replay);
+        *self = game;
+        let replayed = "Replayed ".to_string()
+            + &step.to_string()
+            + " of "
+            + &length.to_string()
+            + " commands";
+        match failed {
+            Some(failed) => {
+                Err(replayed + ", command " + &(step + 1).to_string() + " failed: " + &failed)
+            }
+            None => Ok(replayed),
+        }
+    }
+
+    pub fn get_log(&self) -> &[log::LogEntry] {
+        &self.log
+    }
+
+    // lets remote commands wake up whoever is running the game, the ui repaints with it
+    pub fn set_waker(&mut self, waker: std::sync::Arc<dyn Fn() + Send + Sync>) {
+        self.waker = Some(waker);
+    }
+
+    // commands sent through the api, to be run with process_command and replied to
+    pub fn take_remote_commands(&mut self) -> Vec<server::RemoteCommand> {
+        match &self.api {
+            Some(api) => api.take_commands(),
+            None => Vec::new(),
+        }
+    }
+
+    // shuts down the player view server and the command api, for when the game goes away
+    pub fn stop_servers(&mut self) {
+        if let Some(server) = self.server.take() {
+            server.stop();
+        }
+        if let Some(api) = self.api.take() {
+            api.stop();
+        }
+    }
+
+    // highest initiative first, entities without one go last in list order,
+    // events lose ties like lair actions do,
+    // once the order was changed by hand new arrivals are slotted in by initiative
+    fn turn_order(&self) -> Vec<usize> {
+        let mut by_initiative = Vec::from_iter(0..self.entities.len());
+        by_initiative.sort_by_key(|&i| {
+            (
+                std::cmp::Reverse(self.entities[i].initiative),
+                self.entities[i].event.is_some(),
+            )
+        });
+        let mut order: Vec<usize> = self
+            .manual_order
+            .iter()
+            .filter_map(|name| self.entities.iter().position(|x| &x.name == name))
+            .collect();
+        if order.is_empty() {
+            return by_initiative;
+        }
+        for i in by_initiative {
+            if order.contains(&i) {
+                continue;
+            }
+            match order
+                .iter()
+                .position(|&j| self.entities[j].initiative < self.entities[i].initiative)
+            {
+                Some(position) => order.insert(position, i),
+                None => order.push(i),
+            }
+        }
+        order
+    }
+
+    // once anyone has rolled initiative entities are shown in turn order instead of by faction
+    pub fn in_turn_order(&self) -> bool {
+        !self.manual_order.is_empty() || self.entities.iter().any(|x| x.initiative.is_some())
+    }
+
+    fn find_entity(&self, name: &str) -> Result<usize, String> {
+        self.entities
+            .iter()
+            .position(|x| x.name.to_lowercase() == name.to_lowercase())
+            .ok_or_else(|| match self.suggest_target(name) {
+                Some(suggestion) => {
+                    "No entity named ".to_string() + name + ", did you mean " + &suggestion + "?"
+                }
+                None => "No entity named ".to_string() + name,
+            })
+    }
+
+    // moves an entity to a 0 based position in the turn order
+    fn move_turn(&mut self, name: &str, position: usize) -> Result<(), String> {
+        let index = self.find_entity(name)?;
+        let mut order: Vec<String> = self
+            .turn_order()
+            .iter()
+            .filter(|&&i| i != index)
+            .map(|&i| self.entities[i].name.clone())
+            .collect();
+        order.insert(position.min(order.len()), self.entities[index].name.clone());
+        self.manual_order = order;
+        Ok(())
+    }
+
+    fn next_turn(&mut self) -> Result<String, String> {
+        let order = self.turn_order();
+        if order.is_empty() {
+            return Err("No entities to take a turn".to_string());
+        }
+        let current = self.turn.as_ref().and_then(|name| {
+            order
+                .iter()
+                .position(|&i| self.entities[i].name.to_lowercase() == name.to_lowercase())
+        });
+        let mut result = String::new();
+        if self.turn.is_none() {
+            for entity in self.entities.iter_mut() {
+                entity.encounter_stats = CombatStats::default();
+            }
+        }
+        let next = match current {
+            Some(position) if position + 1 < order.len() => order[position + 1],
+            _ => {
+                self.round += 1;
+                if self.round > 1 {
+                    for entity in self.entities.iter_mut() {
+                        if entity.event.is_none() && !entity.is_down() {
+                            entity.record(|stats| stats.rounds_survived += 1);
+                        }
+                    }
+                    let expired = self.advance_clock(6);
+                    if !expired.is_empty() {
+                        result.push_str("Effects ended: ");
+                        result.push_str(&expired.join(", "));
+                        result.push('\n');
+                    }
+                }
+                for entity in self.entities.iter_mut() {
+                    entity.reset_resources(&[ResetCadence::Round]);
+                }
+                order[0]
+            }
+        };
+        let entity = &mut self.entities[next];
+        entity.reset_resources(&[ResetCadence::Turn]);
+        entity.actions = ActionEconomy::default();
+        self.turn = Some(entity.name.clone());
+        result.push_str("Round ");
+        result.push_str(&self.round.to_string());
+        result.push_str(", ");
+        result.push_str(&entity.name);
+        match &entity.event {
+            Some(reminder) => {
+                result.push_str(": ");
+                result.push_str(reminder);
+            }
+            None => result.push_str("'s turn"),
+        }
+        Ok(result)
+    }
+
+    // finds a faction by id, display name or abbreviation
+    fn find_faction(&self, name: &str) -> Option<&Faction> {
+        self.factions.iter().find(|x| x.matches(name))
+    }
+
+    // id for a faction name typed by the user, unknown if nothing matches
+    fn faction_id(&self, name: &str) -> String {
+        match self.find_faction(name) {
+            Some(faction) => faction.id.clone(),
+            None => UNKNOWN_FACTION.to_string(),
+        }
+    }
+
+    pub fn faction(&self, id: &str) -> &Faction {
+        self.factions
+            .iter()
+            .find(|x| x.id == id)
+            .or_else(|| self.factions.iter().find(|x| x.id == UNKNOWN_FACTION))
+            .expect("the unknown faction always exists")
+    }
+
+    // matches names case insensitively with * wildcards and @ selectors, whole factions
+    // are only selected with @<faction>, initiative events are never selected
+    fn select_entities(&self, names: &[&str]) -> Vec<usize> {
+        let mut selected = Vec::new();
+        for name in names {
+            for i in self.select_entity(name) {
+                if !selected.contains(&i) {
+                    selected.push(i);
+                }
+            }
+        }
+        selected.sort();
+        selected
+    }
+
+    // everything a single name or selector stands for:
+    // @all, @turn, @bloodied, @down, @hidden, @has:<effect>, @tag:<tag>, @note:<text>
+    // and @<faction>
+    fn select_entity(&self, name: &str) -> Vec<usize> {
+        let candidates = (0..self.entities.len()).filter(|&i| self.entities[i].event.is_none());
+        let Some(selector) = name.strip_prefix('@') else {
+            return candidates
+                .filter(|&i| name_matches(name, &self.entities[i].name))
+                .collect();
+        };
+        let (kind, value) = selector.split_once(':').unwrap_or((selector, ""));
+        match kind.to_lowercase().as_str() {
+            "all" => candidates.collect(),
+            "turn" => candidates
+                .filter(|&i| self.turn.as_ref() == Some(&self.entities[i].name))
+                .collect(),
+            "bloodied" => candidates
+                .filter(|&i| {
+                    let entity = &self.entities[i];
+                    entity.max_hp.is_some_and(|max_hp| {
+                        // widened so large hit point totals can't overflow
+                        !entity.is_down()
+                            && max_hp.saturating_sub(entity.damage_taken) as u32 * 2
+                                <= max_hp as u32
+                    })
+                })
+                .collect(),
+            "down" => candidates.filter(|&i| self.entities[i].is_down()).collect(),
+            "hidden" => candidates.filter(|&i| self.entities[i].hidden).collect(),
+            "tag" => candidates
+                .filter(|&i| {
+                    self.entities[i]
+                        .tags
+                        .iter()
+                        .any(|x| x.eq_ignore_ascii_case(value))
+                })
+                .collect(),
+            "note" => candidates
+                .filter(|&i| {
+                    self.entities[i]
+                        .notes
+                        .to_lowercase()
+                        .contains(&value.to_lowercase())
+                })
+                .collect(),
+            "has" => candidates
+                .filter(|&i| {
+                    self.entities[i]
+                        .status_effects
+                        .iter()
+                        .any(|x| x.name.eq_ignore_ascii_case(value))
+                })
+                .collect(),
+            _ => match self.find_faction(selector) {
+                Some(faction) => candidates
+                    .filter(|&i| self.entities[i].team == faction.id)
+                    .collect(),
+                None => Vec::new(),
+            },
+        }
+    }
+
+    // like select_entities but names that match nothing are reported, as a warning unless
+    // in strict mode or nothing matched at all
+    fn targets(&mut self, names: &[&str]) -> Result<Vec<usize>, String> {
+        let targets = self.select_entities(names);
+        let unmatched = self.unmatched_targets(names);
+        if !unmatched.is_empty() {
+            let message = "No match for ".to_string() + &unmatched.join(", ");
+            if targets.is_empty() || self.strict {
+                return Err(message);
+            }
+            self.warnings.push(message);
+        }
+        if targets.is_empty() {
+            return Err("No entities matched ".to_string() + &names.join(" "));
+        }
+        Ok(targets)
+    }
+
+    // names and selectors that don't stand for anything, with a suggestion when one is close
+    fn unmatched_targets(&self, names: &[&str]) -> Vec<String> {
+        let mut unmatched = Vec::new();
+        for name in names {
+            let is_event = self
+                .entities
+                .iter()
+                .any(|x| x.event.is_some() && name_matches(name, &x.name));
+            if is_event || !self.select_entity(name).is_empty() {
+                continue;
+            }
+            match self.suggest_target(name) {
+                Some(suggestion) => {
+                    unmatched.push(name.to_string() + " (did you mean " + &suggestion + "?)")
+                }
+                None => unmatched.push(name.to_string()),
+            }
+        }
+        unmatched
+    }
+
+    fn suggest_target(&self, name: &str) -> Option<String> {
+        if let Some(faction) = self.find_faction(name) {
+            return Some("@".to_string() + &faction.id);
+        }
+        let mut candidates = Vec::from_iter(self.entities.iter().map(|x| x.name.clone()));
+        if let Some(typed) = name.strip_prefix('@') {
+            for selector in ["all", "turn", "bloodied", "down", "hidden"] {
+                candidates.push("@".to_string() + selector);
+            }
+            // selectors with a value keep what was typed after the :, one of these
+            // that matched nobody isn't swapped for another
+            let (kind, value) = typed.split_once(':').unwrap_or((typed, ""));
+            if !["has", "tag", "note"].contains(&kind.to_lowercase().as_str()) {
+                for selector in ["has:", "tag:", "note:"] {
+                    candidates.push("@".to_string() + selector + value);
+                }
+            }
+            for faction in self.factions.iter() {
+                candidates.push("@".to_string() + &faction.id);
+            }
+        }
+        // close enough is about a third of the name being off, a selector that's spelled
+        // right but matches nobody isn't suggested back to itself
+        let allowed = (name.chars().count() / 3).max(1);
+        candidates
+            .into_iter()
+            .map(|x| (edit_distance(name, &x), x))
+            .filter(|(distance, _)| *distance > 0 && *distance <= allowed)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, x)| x)
+    }
+
+    // names of the selected entities, to say what a command actually affected
+    fn target_names(&self, targets: &[usize]) -> String {
+        Vec::from_iter(targets.iter().map(|&i| self.entities[i].name.clone())).join(", ")
+    }
+
+    fn check_not_events(&self, names: &[&str]) -> Result<(), String> {
+        for entity in self.entities.iter() {
+            if entity.event.is_some()
+                && names
+                    .iter()
+                    .any(|x| x.to_lowercase() == entity.name.to_lowercase())
+            {
+                return Err(entity.name.clone() + " is an initiative event and can't be targeted");
+            }
+        }
+        Ok(())
+    }
+
+    // splits a trailing "by <source>" off the targets of damage and healing,
+    // without one it's credited to whoever's turn it is
+    fn split_source<'a>(
+        &self,
+        targets: &[&'a str],
+    ) -> Result<(Vec<&'a str>, Option<usize>), String> {
+        match targets.iter().position(|x| x.eq_ignore_ascii_case("by")) {
+            Some(position) => {
+                if position == 0 || position + 2 != targets.len() {
+                    return Err("Expected <names[]> by <source>".to_string());
+                }
+                let source = self.find_entity(targets[position + 1])?;
+                if self.entities[source].event.is_some() {
+                    return Err(self.entities[source].name.clone()
+                        + " is an initiative event and can't be a source");
+                }
+                Ok((targets[..position].to_vec(), Some(source)))
+            }
+            None => {
+                let source = self
+                    .turn
+                    .as_ref()
+                    .and_then(|turn| self.find_entity(turn).ok())
+                    .filter(|&i| self.entities[i].event.is_none());
+                Ok((targets.to_vec(), source))
+            }
+        }
+    }
+
+    // moves the game clock forward and drops every effect that has run out,
+    // returning "<effect> on <entity>" for each one removed
+    fn advance_clock(&mut self, seconds: u32) -> Vec<String> {
+        // a clock that can't move on just stays at the end of time
+        self.clock = self.clock.saturating_add(seconds);
+        let clock = self.clock;
+        let mut expired = Vec::new();
+        for entity in self.entities.iter_mut() {
+            for effect in entity.status_effects.clone() {
+                if effect.expired(clock) {
+                    // uptime is counted when an effect ends, one that runs out lasted its duration
+                    if let Some(duration) = effect.duration.seconds() {
+                        entity.record(|stats| stats.add_uptime(&effect.name, duration));
+                    }
+                    expired.push(effect.name.clone() + " on " + &entity.name);
+                }
+            }
+            entity.status_effects.retain(|x| !x.expired(clock));
+        }
+        expired
+    }
+
+    // hit_dice is the most each of them spends healing on a short rest
+    fn rest(&mut self, length: RestLength, targets: Vec<usize>, hit_dice: u16) -> String {
+        let mut summary = match length {
+            RestLength::Short => "Short rest:".to_string(),
+            RestLength::Long => "Long rest:".to_string(),
+        };
+        for i in targets {
+            let entity = &mut self.entities[i];
+            let mut changes: Vec<String> = Vec::new();
+            let restored = match length {
+                RestLength::Short => entity.reset_resources(&[ResetCadence::ShortRest]),
+                RestLength::Long => {
+                    entity.reset_resources(&[ResetCadence::ShortRest, ResetCadence::LongRest])
+                }
+            };
+            if !restored.is_empty() {
+                changes.push("restored ".to_string() + &restored.join(", "));
+            }
+            match length {
+                RestLength::Short => {
+                    if let Some(die) = entity.hit_dice {
+                        let mut spent = 0;
+                        let mut healed = 0;
+                        while entity.damage_taken > 0
+                            && entity.hit_dice_remaining > 0
+                            && spent < hit_dice
+                        {
+                            let roll = dice::DiceExpression { count: 1, ..die }
+                                .roll(&mut self.rng)
+                                .min(entity.damage_taken);
+                            entity.damage_taken -= roll;
+                            entity.hit_dice_remaining -= 1;
+                            healed += roll;
+                            spent += 1;
+                        }
+                        if spent > 0 {
+                            changes.push(
+                                "spent ".to_string()
+                                    + &spent.to_string()
+                                    + " hit dice healing "
+                                    + &healed.to_string()
+                                    + " ("
+                                    + &entity.hit_dice_remaining.to_string()
+                                    + " left)",
+                            );
+                        }
+                    }
+                }
+                RestLength::Long => {
+                    if entity.damage_taken > 0 {
+                        changes.push("healed ".to_string() + &entity.damage_taken.to_string());
+                        entity.damage_taken = 0;
+                    }
+                    if let Some(hit_dice) = entity.hit_dice {
+                        let regained = (hit_dice.count / 2)
+                            .max(1)
+                            .min(hit_dice.count - entity.hit_dice_remaining);
+                        if regained > 0 {
+                            entity.hit_dice_remaining += regained;
+                            changes.push(
+                                "regained ".to_string() + &regained.to_string() + " hit dice",
+                            );
+                        }
+                    }
+                    if entity.exhaustion > 0 {
+                        entity.exhaustion -= 1;
+                        changes.push(
+                            "exhaustion reduced to ".to_string() + &entity.exhaustion.to_string(),
+                        );
+                    }
+                }
+            }
+            summary.push('\n');
+            summary.push_str(&entity.name);
+            summary.push_str(": ");
+            if changes.is_empty() {
+                summary.push_str("no change");
+            } else {
+                summary.push_str(&changes.join(", "));
+            }
+        }
+        let expired = self.advance_clock(length.seconds());
+        if !expired.is_empty() {
+            summary.push_str("\nEffects ended: ");
+            summary.push_str(&expired.join(", "));
+        }
+        summary
+    }
+
+    pub fn get_turn(&self) -> Option<&String> {
+        self.turn.as_ref()
+    }
+
+    pub fn get_round(&self) -> u16 {
+        self.round
+    }
+
+    // entities in display order, grouped by faction priority
+    pub fn get_entities(&mut self) -> &[Entity] {
+        self.sort_entities();
+        &self.entities
+    }
+
+    fn sort_entities(&mut self) {
+        if self.in_turn_order() {
+            let order = self.turn_order();
+            let mut entities = Vec::new();
+            for i in order {
+                entities.push(self.entities[i].clone());
+            }
+            self.entities = entities;
+            return;
+        }
+        let factions = self.factions.clone();
+        let priority = |id: &String| factions.iter().find(|x| &x.id == id).map(|x| x.priority);
+        // factions sharing a priority stay grouped by id
+        self.entities
+            .sort_by_key(|a| (priority(&a.team).unwrap_or(i16::MAX), a.team.clone()));
+    }
+
+    pub fn get_entities_list(&mut self) -> String {
+        let mut list = String::new();
+        if let Some(turn) = &self.turn {
+            list.push_str("Round ");
+            list.push_str(&self.round.to_string());
+            list.push_str(", ");
+            list.push_str(turn);
+            list.push_str("'s turn\n");
+        }
+        self.sort_entities();
+        let in_turn_order = self.in_turn_order();
+        if in_turn_order {
+            list.push_str("\nTurn Order\n");
+        }
+        let mut last_team = None;
+        for entity in self.entities.iter() {
+            if !in_turn_order && last_team != Some(&entity.team) {
+                list.push('\n');
+                list.push_str(&self.faction(&entity.team).to_string());
+                list.push('\n');
+            }
+            last_team = Some(&entity.team);
+            if self.turn.as_ref() == Some(&entity.name) {
+                list.push_str("> ");
+            }
+            if let Some(reminder) = &entity.event {
+                list.push_str(&entity.name);
+                list.push_str(" (");
+                list.push_str(&entity.initiative.unwrap_or_default().to_string());
+                list.push_str("): ");
+                list.push_str(reminder);
+                list.push('\n');
+                continue;
+            }
+            if self.turn.is_some() {
+                list.push_str(&entity.actions.to_string());
+                list.push(' ');
+            }
+            list.push_str(&entity.name);
+            if entity.hidden {
+                list.push_str(" (hidden)");
+            }
+            if in_turn_order {
+                list.push_str(" [");
+                list.push_str(&self.faction(&entity.team).to_string());
+                list.push(']');
+            }
+            if let Some(initiative) = entity.initiative {
+                list.push_str(" (");
+                list.push_str(&initiative.to_string());
+                list.push(')');
+            }
+            list.push_str(", Damage Taken: ");
+            list.push_str(&entity.damage_taken.to_string());
+            if let Some(max_hp) = entity.max_hp {
+                list.push_str(", HP: ");
+                list.push_str(&max_hp.saturating_sub(entity.damage_taken).to_string());
+                list.push('/');
+                list.push_str(&max_hp.to_string());
+            }
+            if let Some(hit_dice) = entity.hit_dice {
+                list.push_str(", Hit Dice: ");
+                list.push_str(&entity.hit_dice_remaining.to_string());
+                list.push('/');
+                list.push_str(&hit_dice.to_string());
+            }
+            let stat_block = entity.stat_block();
+            if !stat_block.is_empty() {
+                list.push_str(", ");
+                list.push_str(&stat_block);
+            }
+            if let Some(level) = entity.level {
+                list.push_str(", Level: ");
+                list.push_str(&level.to_string());
+            }
+            if let Some(xp) = entity.xp {
+                list.push_str(", XP Value: ");
+                list.push_str(&xp.to_string());
+            }
+            if entity.experience > 0 {
+                list.push_str(", Experience: ");
+                list.push_str(&entity.experience.to_string());
+            }
+            if entity.exhaustion > 0 {
+                list.push_str(", Exhaustion: ");
+                list.push_str(&entity.exhaustion.to_string());
+            }
+            if !entity.resources.is_empty() {
+                list.push_str(", ");
+                for resource in entity.resources.iter() {
+                    list.push_str(&resource.name);
+                    list.push(' ');
+                    list.push_str(&resource.current.to_string());
+                    list.push('/');
+                    list.push_str(&resource.max.to_string());
+                    list.push(' ');
+                }
+                list.pop();
+            }
+            if !entity.tags.is_empty() {
+                list.push_str(", Tags: ");
+                list.push_str(&entity.tags.join(" "));
+            }
+            if !entity.notes.is_empty() {
+                list.push_str(", Notes: ");
+                list.push_str(&entity.notes.replace('\n', " / "));
+            }
+            if !entity.status_effects.is_empty() {
+                list.push_str(", Status Effects: ");
+            }
+            for effect in entity.status_effects.iter() {
+                list.push_str(&effect.name);
+                if effect.secret {
+                    list.push_str(" (secret)");
+                }
+                list.push_str(", ");
+                list.push_str(&effect.duration());
+                list.push_str("; ");
+            }
+            list.push('\n');
+        }
+        list
+    }
+
+    // the table facing view, hidden entities, secret effects and exact hit points
+    // of anyone outside the party are left out
+    pub fn get_player_view(&mut self) -> String {
+        let mut list = String::new();
+        self.sort_entities();
+        if let Some(turn) = &self.turn {
+            list.push_str("Round ");
+            list.push_str(&self.round.to_string());
+            if let Ok(i) = self.find_entity(turn) {
+                if !self.entities[i].hidden {
+                    list.push_str(", ");
+                    list.push_str(turn);
+                    list.push_str("'s turn");
+                }
+            }
+            list.push('\n');
+        }
+        for entity in self.entities.iter().filter(|x| !x.hidden) {
+            list.push('\n');
+            if self.turn.as_ref() == Some(&entity.name) {
+                list.push_str("> ");
+            }
+            list.push_str(&entity.name);
+            if let Some(reminder) = &entity.event {
+                list.push_str(": ");
+                list.push_str(reminder);
+                continue;
+            }
+            list.push_str(" [");
+            list.push_str(&self.faction(&entity.team).to_string());
+            list.push_str("], ");
+            list.push_str(&entity.public_health());
+            let effects: Vec<&str> = entity
+                .status_effects
+                .iter()
+                .filter(|x| !x.secret)
+                .map(|x| x.name.as_str())
+                .collect();
+            if !effects.is_empty() {
+                list.push_str(", ");
+                list.push_str(&effects.join(", "));
+            }
+        }
+        list
+    }
+
+    // same information as the player view, as json for the server
+    pub fn get_player_state_json(&mut self) -> String {
+        self.sort_entities();
+        let turn = self
+            .turn
+            .as_ref()
+            .filter(|x| self.find_entity(x).is_ok_and(|i| !self.entities[i].hidden));
+        let mut json = "{\"round\":".to_string() + &self.round.to_string() + ",\"turn\":";
+        match turn {
+            Some(turn) => json.push_str(&server::json_string(turn)),
+            None => json.push_str("null"),
+        }
+        json.push_str(",\"entities\":[");
+        let mut first = true;
+        for entity in self.entities.iter().filter(|x| !x.hidden) {
+            if !first {
+                json.push(',');
+            }
+            first = false;
+            let faction = self.faction(&entity.team);
+            json.push_str("{\"name\":");
+            json.push_str(&server::json_string(&entity.name));
+            json.push_str(",\"faction\":");
+            json.push_str(&server::json_string(&faction.to_string()));
+            json.push_str(",\"color\":");
+            let color = "#".to_string() + &faction.color.map(|x| format!("{:02x}", x)).concat();
+            json.push_str(&server::json_string(&color));
+            json.push_str(",\"turn\":");
+            json.push_str(&(self.turn.as_ref() == Some(&entity.name)).to_string());
+            json.push_str(",\"event\":");
+            match &entity.event {
+                Some(reminder) => json.push_str(&server::json_string(reminder)),
+                None => json.push_str("null"),
+            }
+            json.push_str(",\"health\":");
+            json.push_str(&server::json_string(&entity.public_health()));
+            json.push_str(",\"effects\":[");
+            let effects: Vec<String> = entity
+                .status_effects
+                .iter()
+                .filter(|x| !x.secret)
+                .map(|x| server::json_string(&x.name))
+                .collect();
+            json.push_str(&effects.join(","));
+            json.push_str("]}");
+        }
+        json.push_str("]}");
+        json
+    }
+
+    pub fn get_faction_ids(&self) -> Vec<String> {
+        Vec::from_iter(self.factions.iter().map(|x| x.id.clone()))
+    }
+
+    pub fn get_template_names(&mut self) -> Vec<String> {
+        Vec::from_iter(self.templates().iter().map(|x| x.key()))
+    }
+
+    // every effect currently on someone, once each
+    pub fn get_effect_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for effect in self.entities.iter().flat_map(|x| x.status_effects.iter()) {
+            if !names.contains(&effect.name) {
+                names.push(effect.name.clone());
+            }
+        }
+        names
+    }
+
+    pub fn get_tag_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for tag in self.entities.iter().flat_map(|x| x.tags.iter()) {
+            if !names.contains(tag) {
+                names.push(tag.clone());
+            }
+        }
+        names
+    }
+
+    pub fn get_resource_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for resource in self.entities.iter().flat_map(|x| x.resources.iter()) {
+            if !names.contains(&resource.name) {
+                names.push(resource.name.clone());
+            }
+        }
+        names
+    }
+
+    pub fn get_encounter_names(&self) -> Vec<String> {
+        encounter::list_encounters()
+    }
+
+    // files in the saves folder, as typed for load
+    pub fn get_save_names(&self) -> Vec<String> {
+        text_file_names("saves")
+    }
+
+    pub fn get_campaign_names(&self) -> Vec<String> {
+        text_file_names(CAMPAIGN_DIRECTORY)
+    }
+
+    pub fn get_tab_save_names(&self) -> Vec<String> {
+        text_file_names(TAB_DIRECTORY)
+    }
+
+    pub fn process_command(&mut self, command: String) -> Result<String, String> {
+        let hp_before = Vec::from_iter(
+            self.entities
+                .iter()
+                .map(|e| (e.name.clone(), e.damage_taken)),
+        );
+        let round = self.round;
+        let turn = self.turn.clone();
+        self.last_source = None;
+        self.warnings.clear();
+        let mut result = self.run_command(command.clone());
+        let file = self.file_read.take();
+        if let Ok(message) = &mut result {
+            for warning in self.warnings.drain(..) {
+                message.push_str("\nWarning: ");
+                message.push_str(&warning);
+            }
+        }
+        let first = argumment_abreviations(command.split_whitespace().next().unwrap_or(""));
+        // running anything else while stepping through a replay continues from that point
+        if self.replay.is_some() && replayable(first) {
+            self.replay = None;
+        }
+        if !first.is_empty() && !matches!(first, "help" | "replay" | "replay_step") {
+            let mut hp_changes = Vec::new();
+            for (name, damage_before) in hp_before {
+                if let Some(entity) = self.entities.iter().find(|e| e.name == name) {
+                    if entity.damage_taken != damage_before {
+                        hp_changes.push((name, damage_before as i32 - entity.damage_taken as i32));
+                    }
+                }
+            }
+            self.log.push(log::LogEntry {
+                time: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                round,
+                turn,
+                source: self.last_source.take(),
+                command: redact(command.trim()),
+                // the result of api shows the token
+                outcome: match first {
+                    "api" => result.clone().map(|_| "Started command API".to_string()),
+                    _ => result.clone(),
+                },
+                hp_changes,
+                file,
+            });
+        }
+        if let Some(server) = self.server.clone() {
+            server.publish(self.get_player_state_json());
+        }
+        result
+    }
+
+    fn run_command(&mut self, command: String) -> Result<String, String> {
+        // options like --secret can go anywhere in the command
+        let (flags, args): (Vec<&str>, Vec<&str>) =
+            command.split(" ").partition(|x| x.starts_with("--"));
+        if args.is_empty() {
+            return Err("Unrecognized command use help to list commands".to_string());
+        }
+        let accepted = command_flags(argumment_abreviations(args[0]));
+        // note text is taken as typed, words in it can start with -- too
+        let is_note = argumment_abreviations(args[0]) == "note";
+        if let Some(flag) = flags.iter().find(|x| !is_note && !accepted.contains(x)) {
+            return Err("Unknown option ".to_string() + flag + " for " + args[0]);
+        }
+        match argumment_abreviations(args[0]) {
+            "add_effect" => {
+                if args.len() < 4 {
+                    return Err("Not enough arguments".to_string());
+                }
+                self.check_not_events(&args[4..])?;
+                let effect = args[1].to_string();
+                // saves keep uptime as name=seconds, separated by , and |
+                if effect.contains(['=', ',', '|']) {
+                    return Err("Effect names can't contain =, , or |".to_string());
+                }
+                let duration = args[2].parse().unwrap_or(0);
+                let duration_unit = DurationUnit::from(args[3].to_string());
+                let targets = self.targets(&args[4..])?;
+                for &i in targets.iter() {
+                    self.entities[i].status_effects.push(StatusEffect {
+                        name: effect.clone(),
+                        duration: Duration {
+                            length: duration,
+                            unit: duration_unit,
+                        },
+                        applied_at: self.clock,
+                        secret: flags.contains(&"--secret"),
+                    });
+                }
+                Ok("Added ".to_string() + &effect + " to " + &self.target_names(&targets))
+            }
+            "remove_effect" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let effect = args[1].to_string();
+                let targets = self.targets(&args[2..])?;
+                let clock = self.clock;
+                let in_combat = self.round > 0;
+                for &i in targets.iter() {
+                    let entity = &mut self.entities[i];
+                    for removed in entity.status_effects.clone() {
+                        // effects with an unknown unit have no uptime to count
+                        if removed.name != effect || removed.duration.seconds().is_none() {
+                            continue;
+                        }
+                        // taken off during a fight it was still up for part of a round
+                        let mut active = clock.saturating_sub(removed.applied_at);
+                        if in_combat {
+                            active = active.max(6);
+                        }
+                        entity.record(|stats| stats.add_uptime(&effect, active));
+                    }
+                    entity.status_effects.retain(|x| x.name != effect);
+                }
+                Ok("Removed ".to_string() + &effect + " from " + &self.target_names(&targets))
+            }
+            "add_entity" => {
+                if args.len() < 2 {
+                    return Err("Not enough arguments".to_string());
+                }
+                check_entity_name(args[1])?;
+                let team = if args.len() > 2 {
+                    self.faction_id(args[2])
+                } else {
+                    UNKNOWN_FACTION.to_string()
+                };
+                // the rest can be a number of copies like x6, hit dice to roll hp
+                // with and a stat block, by default the one matching the name
+                let mut count = None;
+                let mut hp_dice = None;
+                let mut template_name = None;
+                for arg in args.iter().skip(3) {
+                    if let Some(copies) = arg
+                        .strip_prefix(['x', 'X'])
+                        .and_then(|x| x.parse::<usize>().ok())
+                    {
+                        count = Some(copies);
+                    } else if let Ok(dice) = dice::DiceExpression::parse(arg) {
+                        hp_dice = Some(dice);
+                    } else {
+                        template_name = Some(*arg);
+                    }
+                }
+                let template = self
+                    .templates()
+                    .iter()
+                    .find(|x| x.matches(template_name.unwrap_or(args[1])))
+                    .cloned();
+                if let (None, Some(name)) = (&template, template_name) {
+                    return Err("No stat block named ".to_string() + name);
+                }
+                if flags.contains(&"--roll") && hp_dice.is_none() {
+                    hp_dice = template.as_ref().and_then(|x| x.hit_points_roll);
+                    if hp_dice.is_none() {
+                        return Err("No hit dice to roll hp with".to_string());
+                    }
+                }
+                let letters = flags.contains(&"--letters");
+                let names = match count {
+                    None => {
+                        if self.find_entity(args[1]).is_ok() {
+                            return Err("This entity already exists".to_string());
+                        }
+                        vec![args[1].to_string()]
+                    }
+                    Some(count) => {
+                        if count == 0 || count > MAX_COPIES || (letters && count > 26) {
+                            return Err("Invalid number of copies".to_string());
+                        }
+                        // numbering carries on after copies that are already there
+                        let mut names = Vec::new();
+                        let mut n = 1;
+                        while names.len() < count {
+                            if letters && n > 26 {
+                                return Err("Ran out of letters for copies".to_string());
+                            }
+                            let name = args[1].to_string() + "_" + &copy_suffix(n, letters);
+                            if self.find_entity(&name).is_err() {
+                                names.push(name);
+                            }
+                            n += 1;
+                        }
+                        names
+                    }
+                };
+                let mut added = Vec::new();
+                for name in names {
+                    let mut entity = Entity::new(name.clone(), team.clone());
+                    entity.hidden = flags.contains(&"--hidden");
+                    if let Some(template) = &template {
+                        entity.apply_template(template);
+                    }
+                    match hp_dice {
+                        Some(dice) => {
+                            let hp = dice.roll(&mut self.rng).max(1);
+                            entity.max_hp = Some(hp);
+                            added.push(name + " (" + &hp.to_string() + " HP)");
+                        }
+                        None => added.push(name),
+                    }
+                    self.entities.push(entity);
+                }
+                let mut result = "Added ".to_string() + &added.join(", ");
+                if let Some(template) = template {
+                    result.push_str(" from ");
+                    result.push_str(&template.name);
+                }
+                Ok(result)
+            }
+            "add_event" => {
+                if args.len() < 4 {
+                    return Err("Not enough arguments".to_string());
+                }
+                check_entity_name(args[1])?;
+                for entity in self.entities.iter() {
+                    if entity.name.to_lowercase() == args[1].to_string().to_lowercase() {
+                        return Err("This entity already exists".to_string());
+                    }
+                }
+                let initiative = args[2].parse::<i16>().map_err(|e| e.to_string())?;
+                let reminder = args[3..].join(" ");
+                // saves keep events as event|name|initiative|reminder
+                if reminder.contains('|') {
+                    return Err("Reminders can't contain |".to_string());
+                }
+                self.entities
+                    .push(Entity::new_event(args[1].to_string(), initiative, reminder));
+                Ok("Added event".to_string())
+            }
+            "remove_entity" => {
+                if args.len() < 2 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let mut targets = self.select_entities(&args[1..]);
+                for (i, entity) in self.entities.iter().enumerate() {
+                    if entity.event.is_some()
+                        && args[1..].iter().any(|x| name_matches(x, &entity.name))
+                    {
+                        targets.push(i);
+                    }
+                }
+                let unmatched = self.unmatched_targets(&args[1..]);
+                if !unmatched.is_empty() {
+                    let message = "No match for ".to_string() + &unmatched.join(", ");
+                    if targets.is_empty() || self.strict {
+                        return Err(message);
+                    }
+                    self.warnings.push(message);
+                }
+                if targets.is_empty() {
+                    return Err("No entities matched ".to_string() + &args[1..].join(" "));
+                }
+                targets.sort();
+                targets.dedup();
+                let removed = self.target_names(&targets);
+                // the turn passes on before its owner goes, so nobody after them is skipped
+                let names: Vec<String> = targets
+                    .iter()
+                    .map(|&i| self.entities[i].name.to_lowercase())
+                    .collect();
+                let mut passed = String::new();
+                for _ in 0..names.len() {
+                    match &self.turn {
+                        Some(turn) if names.contains(&turn.to_lowercase()) => {
+                            passed = self.next_turn()?;
+                        }
+                        _ => break,
+                    }
+                }
+                for &i in targets.iter().rev() {
+                    let entity = self.entities.remove(i);
+                    if self.round > 0 {
+                        self.removed_in_combat.push(entity);
+                    }
+                }
+                if self.entities.is_empty() {
+                    self.turn = None;
+                }
+                let mut result = "Removed ".to_string() + &removed;
+                if !passed.is_empty() {
+                    result.push('\n');
+                    result.push_str(&passed);
+                }
+                Ok(result)
+            }
+            "damage" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let (targets, source) = self.split_source(&args[2..])?;
+                self.check_not_events(&targets)?;
+                let damage = args[1].parse::<u16>();
+                match damage {
+                    Ok(damage_amount) => {
+                        let source_name = source.map(|i| self.entities[i].name.clone());
+                        let targets = self.targets(&targets)?;
+                        let mut result = "Damaged ".to_string() + &self.target_names(&targets);
+                        let mut dealt = 0;
+                        let mut kills = 0;
+                        for &i in targets.iter() {
+                            let entity = &mut self.entities[i];
+                            let was_down = entity.is_down();
+                            entity.damage_taken = entity.damage_taken.saturating_add(damage_amount);
+                            entity.record(|stats| {
+                                stats.damage_taken =
+                                    stats.damage_taken.saturating_add(damage_amount as u32)
+                            });
+                            dealt += damage_amount as u32;
+                            if !was_down && entity.is_down() {
+                                kills += 1;
+                                entity.killed_by = source_name.clone();
+                                if let Some(source_name) = &source_name {
+                                    result.push('\n');
+                                    result.push_str(&entity.name);
+                                    result.push_str(" killed by ");
+                                    result.push_str(source_name);
+                                }
+                            }
+                        }
+                        let crit = flags.contains(&"--crit");
+                        if let Some(source) = source {
+                            self.entities[source].record(|stats| {
+                                stats.damage_dealt = stats.damage_dealt.saturating_add(dealt);
+                                stats.kills += kills;
+                                if crit {
+                                    stats.crits += 1;
+                                }
+                            });
+                        }
+                        self.last_source = source_name;
+                        Ok(result)
+                    }
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            "heal" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let (targets, source) = self.split_source(&args[2..])?;
+                self.check_not_events(&targets)?;
+                let healing = args[1].parse::<u16>();
+                match healing {
+                    Ok(healing_amount) => {
+                        let targets = self.targets(&targets)?;
+                        let mut healed = 0;
+                        for &i in targets.iter() {
+                            let entity = &mut self.entities[i];
+                            healed += entity.damage_taken.min(healing_amount) as u32;
+                            entity.damage_taken =
+                                entity.damage_taken.saturating_sub(healing_amount);
+                            if !entity.is_down() {
+                                entity.killed_by = None;
+                            }
+                        }
+                        if let Some(source) = source {
+                            self.entities[source].record(|stats| {
+                                stats.healing_done = stats.healing_done.saturating_add(healed)
+                            });
+                            self.last_source = Some(self.entities[source].name.clone());
+                        }
+                        Ok("Healed ".to_string() + &self.target_names(&targets))
+                    }
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            "save" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let team = if args[1].to_lowercase() == "all" {
+                    None
+                } else {
+                    match self.find_faction(args[1]) {
+                        Some(faction) => Some(faction.id.clone()),
+                        None => return Err("Unknown faction ".to_string() + args[1]),
+                    }
+                };
+                check_file_name(args[2])?;
+                save(self, args[2].to_string(), team)
+            }
+            "load" => {
+                if args.len() < 2 {
+                    return Err("Not enough arguments".to_string());
+                }
+                check_file_name(args[1])?;
+                match self.load(args[1]) {
+                    Ok(save_file) => {
+                        self.apply_save(save_file);
+                        Ok("Loaded".to_string())
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            "rest" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let length = match args[1].to_lowercase().as_str() {
+                    "short" | "s" => RestLength::Short,
+                    "long" | "l" => RestLength::Long,
+                    _ => return Err("Rest must be short or long".to_string()),
+                };
+                // a short rest can end with how many hit dice each of them spends
+                let (names, dice) = match args[args.len() - 1].parse::<u16>() {
+                    Ok(dice) if length == RestLength::Short && args.len() > 3 => {
+                        (&args[2..args.len() - 1], dice)
+                    }
+                    _ => (&args[2..], 0),
+                };
+                // the whole party usually rests, so a bare faction name means @<faction> here
+                let names =
+                    Vec::from_iter(names.iter().map(|&name| match self.find_faction(name) {
+                        Some(faction) if self.select_entity(name).is_empty() => {
+                            "@".to_string() + &faction.id
+                        }
+                        _ => name.to_string(),
+                    }));
+                let names = Vec::from_iter(names.iter().map(|x| x.as_str()));
+                let targets = self.targets(&names)?;
+                Ok(self.rest(length, targets, dice))
+            }
+            "hit_dice" => {
+                if args.len() < 4 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let count = args[1].parse::<u16>().map_err(|e| e.to_string())?;
+                let hit_dice = dice::DiceExpression {
+                    count,
+                    ..dice::DiceExpression::parse(args[2])?
+                };
+                let targets = self.targets(&args[3..])?;
+                for &i in targets.iter() {
+                    self.entities[i].hit_dice = Some(hit_dice);
+                    self.entities[i].hit_dice_remaining = count;
+                }
+                Ok("Set hit dice for ".to_string() + &self.target_names(&targets))
+            }
+            "exhaustion" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let level = args[1].parse::<u8>().map_err(|e| e.to_string())?;
+                if level > 6 {
+                    return Err("Exhaustion goes from 0 to 6".to_string());
+                }
+                let targets = self.targets(&args[2..])?;
+                for &i in targets.iter() {
+                    self.entities[i].exhaustion = level;
+                }
+                Ok("Set exhaustion for ".to_string() + &self.target_names(&targets))
+            }
+            "resource" => {
+                if args.len() < 5 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let name = args[1].to_string();
+                // saves keep resources as resource|name|current|max|reset
+                if name.contains(['=', ',', '|']) {
+                    return Err("Resource names can't contain =, , or |".to_string());
+                }
+                let max = args[2].parse::<u16>().map_err(|e| e.to_string())?;
+                let reset = ResetCadence::try_from(args[3].to_string())?;
+                let targets = self.targets(&args[4..])?;
+                for &i in targets.iter() {
+                    let entity = &mut self.entities[i];
+                    entity
+                        .resources
+                        .retain(|x| x.name.to_lowercase() != name.to_lowercase());
+                    entity.resources.push(Resource {
+                        name: name.clone(),
+                        current: max,
+                        max,
+                        reset,
+                    });
+                }
+                Ok("Added ".to_string() + &name + " to " + &self.target_names(&targets))
+            }
+            "use" | "restore" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let name = args[1].to_lowercase();
+                // the amount is optional, use defaults to 1 and restore to full
+                let (amount, names) = match args[2].parse::<u16>() {
+                    Ok(amount) if args.len() > 3 => (Some(amount), &args[3..]),
+                    _ => (None, &args[2..]),
+                };
+                let targets = self.targets(names)?;
+                // every target is checked first so a failed use changes nothing
+                let mut missing = Vec::new();
+                for &i in targets.iter() {
+                    let entity = &self.entities[i];
+                    match entity
+                        .resources
+                        .iter()
+                        .find(|x| x.name.to_lowercase() == name)
+                    {
+                        Some(resource) if args[0] == "use" => {
+                            if resource.current < amount.unwrap_or(1) {
+                                missing.push(entity.name.clone());
+                            }
+                        }
+                        Some(_) => (),
+                        None => missing.push(entity.name.clone()),
+                    }
+                }
+                if !missing.is_empty() {
+                    return Err("Not enough ".to_string() + args[1] + " on " + &missing.join(", "));
+                }
+                for &i in targets.iter() {
+                    let entity = &mut self.entities[i];
+                    for resource in entity.resources.iter_mut() {
+                        if resource.name.to_lowercase() != name {
+                            continue;
+                        }
+                        if args[0] == "use" {
+                            resource.current -= amount.unwrap_or(1);
+                        } else {
+                            resource.current = match amount {
+                                Some(amount) => {
+                                    resource.current.saturating_add(amount).min(resource.max)
+                                }
+                                None => resource.max,
+                            };
+                        }
+                    }
+                }
+                if args[0] == "use" {
+                    Ok("Used ".to_string() + args[1] + " for " + &self.target_names(&targets))
+                } else {
+                    Ok("Restored ".to_string() + args[1] + " for " + &self.target_names(&targets))
+                }
+            }
+            "hide" | "reveal" => {
+                if args.len() < 2 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let targets = self.targets(&args[1..])?;
+                for &i in targets.iter() {
+                    self.entities[i].hidden = args[0] == "hide";
+                }
+                if args[0] == "hide" {
+                    Ok("Hid ".to_string() + &self.target_names(&targets))
+                } else {
+                    Ok("Revealed ".to_string() + &self.target_names(&targets))
+                }
+            }
+            "max_hp" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let max_hp = args[1].parse::<u16>().map_err(|e| e.to_string())?;
+                let targets = self.targets(&args[2..])?;
+                for &i in targets.iter() {
+                    self.entities[i].max_hp = Some(max_hp);
                 }
-                let effect = args[1].to_string();
-                let entity_names =
-                    Vec::from_iter(args[2..].iter().map(|x| x.to_string().to_lowercase()));
-                for entity in self.entities.iter_mut() {
-                    if entity_names.contains(&entity.name.to_lowercase()) {
-                        entity.status_effects.retain(|x| x.name != effect);
+                Ok("Set max HP for ".to_string() + &self.target_names(&targets))
+            }
+            "initiative" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                if args[1] == "roll" {
+                    let mut result = "Rolled initiative:".to_string();
+                    for i in self.targets(&args[2..])? {
+                        let roll = self.rng.roll_die(20) as i16;
+                        let entity = &mut self.entities[i];
+                        let initiative = roll + entity.initiative_modifier;
+                        entity.initiative = Some(initiative);
+                        result.push_str(&format!(
+                            "\n{}: {} ({}{:+})",
+                            entity.name, initiative, roll, entity.initiative_modifier
+                        ));
                     }
+                    return Ok(result);
                 }
-                Ok("Removed effects".to_string())
+                let initiative = args[1].parse::<i16>().map_err(|e| e.to_string())?;
+                let targets = self.targets(&args[2..])?;
+                for &i in targets.iter() {
+                    self.entities[i].initiative = Some(initiative);
+                }
+                Ok("Set initiative for ".to_string() + &self.target_names(&targets))
             }
-            "add_entity" => {
+            "next" => self.next_turn(),
+            "move_turn" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let position = args[2].parse::<usize>().map_err(|e| e.to_string())?;
+                self.move_turn(args[1], position.saturating_sub(1))?;
+                Ok("Moved ".to_string() + args[1] + " to position " + args[2])
+            }
+            "delay" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let index = self.find_entity(args[1])?;
+                self.find_entity(args[2])?;
+                let mut result = String::new();
+                // delaying on your own turn hands it to whoever is next
+                if self.turn.as_ref() == Some(&self.entities[index].name) {
+                    result = self.next_turn()? + "\n";
+                }
+                let name = self.entities[index].name.clone();
+                let after = self.entities[self.find_entity(args[2])?].name.clone();
+                let order: Vec<String> = self
+                    .turn_order()
+                    .iter()
+                    .map(|&i| self.entities[i].name.clone())
+                    .filter(|x| x != &name)
+                    .collect();
+                let position = order
+                    .iter()
+                    .position(|x| x == &after)
+                    .unwrap_or(order.len());
+                self.move_turn(&name, position + 1)?;
+                Ok(result + &name + " delays until after " + &after)
+            }
+            "spend" | "regain" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let kind = ActionKind::try_from(args[1].to_string())?;
+                let targets = self.targets(&args[2..])?;
+                for &i in targets.iter() {
+                    *self.entities[i].actions.spent(kind) = args[0] == "spend";
+                }
+                if args[0] == "spend" {
+                    Ok("Spent ".to_string() + args[1] + " for " + &self.target_names(&targets))
+                } else {
+                    Ok("Regained ".to_string() + args[1] + " for " + &self.target_names(&targets))
+                }
+            }
+            "faction" => {
                 if args.len() < 2 {
                     return Err("Not enough arguments".to_string());
                 }
-                for entity in self.entities.iter() {
-                    if entity.name.to_lowercase() == args[1].to_string().to_lowercase() {
-                        return Err("This entity already exists".to_string());
+                check_save_name(args[1])?;
+                let id = args[1].to_lowercase();
+                let existing = self.factions.iter().position(|x| x.id == id);
+                if existing.is_none() {
+                    if let Some(faction) = self.factions.iter().find(|x| x.matches(&id)) {
+                        return Err(args[1].to_string()
+                            + " already refers to "
+                            + &faction.display_name);
+                    }
+                }
+                let mut faction = match existing {
+                    Some(i) => self.factions[i].clone(),
+                    None => Faction::new(&id, args[1], [160, 160, 160], 100),
+                };
+                if args.len() > 2 {
+                    faction.color = parse_color(args[2])?;
+                }
+                if args.len() > 3 {
+                    faction.priority = args[3].parse::<i16>().map_err(|e| e.to_string())?;
+                }
+                if args.len() > 4 {
+                    faction.display_name = args[4..].join(" ");
+                    if faction.display_name.contains('|') {
+                        return Err("Faction names can't contain |".to_string());
+                    }
+                }
+                match existing {
+                    Some(i) => {
+                        self.factions[i] = faction;
+                        Ok("Updated faction".to_string())
+                    }
+                    None => {
+                        self.factions.push(faction);
+                        Ok("Added faction".to_string())
                     }
                 }
-                self.entities.push(Entity::new(
-                    args[1].to_string(),
-                    if args.len() > 2 {
-                        Team::from(args[2].to_string())
-                    } else {
-                        Team::Unknown
-                    },
-                ));
-                Ok("Added entity".to_string())
             }
-            "remove_entity" => {
+            "factions" => {
+                let mut factions = self.factions.clone();
+                factions.sort_by(|a, b| (a.priority, &a.id).cmp(&(b.priority, &b.id)));
+                Ok(factions
+                    .iter()
+                    .map(|x| {
+                        x.id.clone() + " (" + &x.display_name + ", " + &x.priority.to_string() + ")"
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", "))
+            }
+            "serve" => {
+                let port = match args.get(1) {
+                    Some(port) => port.parse::<u16>().map_err(|e| e.to_string())?,
+                    None => 8080,
+                };
+                if let Some(server) = self.server.take() {
+                    server.stop();
+                }
+                let lan = flags.contains(&"--lan");
+                let server = server::Server::start(port, self.get_player_state_json(), lan)?;
+                let port = server.port();
+                self.server = Some(server);
+                if lan {
+                    return Ok("Serving on port ".to_string()
+                        + &port.to_string()
+                        + " to the local network");
+                }
+                Ok("Serving on http://localhost:".to_string() + &port.to_string())
+            }
+            "api" => {
+                let port = match args.get(1) {
+                    Some(port) => port.parse::<u16>().map_err(|e| e.to_string())?,
+                    None => 8081,
+                };
+                let token = match args.get(2) {
+                    Some(token) => token.to_string(),
+                    None => server::new_token(),
+                };
+                if let Some(api) = self.api.take() {
+                    api.stop();
+                }
+                let lan = flags.contains(&"--lan");
+                let api = server::CommandApi::start(port, token.clone(), lan, self.waker.clone())?;
+                let port = api.port();
+                self.api = Some(api);
+                Ok("Command API on port ".to_string() + &port.to_string() + ", token " + &token)
+            }
+            "stop_api" => match self.api.take() {
+                Some(api) => {
+                    api.stop();
+                    Ok("Stopped command API".to_string())
+                }
+                None => Err("Command API isn't running".to_string()),
+            },
+            "stop_server" => match self.server.take() {
+                Some(server) => {
+                    server.stop();
+                    Ok("Stopped server".to_string())
+                }
+                None => Err("Server isn't running".to_string()),
+            },
+            "export_log" => {
                 if args.len() < 2 {
                     return Err("Not enough arguments".to_string());
                 }
-                self.entities
-                    .retain(|x| x.name.to_lowercase() != args[1].to_lowercase());
-                Ok("Removed entity".to_string())
+                let filename = args[1..].join(" ");
+                let contents = if filename.to_lowercase().ends_with(".html")
+                    || filename.to_lowercase().ends_with(".htm")
+                {
+                    log::to_html(&self.log)
+                } else {
+                    log::to_markdown(&self.log)
+                };
+                match std::fs::write(&filename, contents) {
+                    Ok(_) => Ok("Exported log to ".to_string() + &filename),
+                    Err(e) => Err(e.to_string()),
+                }
             }
-            "damage" => {
-                if args.len() < 3 {
+            "save_log" => {
+                if args.len() < 2 {
                     return Err("Not enough arguments".to_string());
                 }
-                let damage = args[1].parse::<u16>();
-                match damage {
-                    Ok(damage_amount) => {
-                        let entity_names =
-                            Vec::from_iter(args[2..].iter().map(|x| x.to_string().to_lowercase()));
-                        for entity in self.entities.iter_mut() {
-                            if entity_names.contains(&entity.name.to_lowercase()) {
-                                entity.damage_taken += damage_amount;
-                            }
-                        }
-                        Ok("Damaged entities".to_string())
-                    }
+                // commands that failed didn't change anything
+                let commands = Vec::from_iter(self.log.iter().filter(|entry| {
+                    entry.outcome.is_ok()
+                        && replayable(argumment_abreviations(
+                            entry.command.split_whitespace().next().unwrap_or(""),
+                        ))
+                }));
+                match std::fs::write(args[1], log::to_replay(self.seed, &commands)) {
+                    Ok(_) => Ok("Saved ".to_string() + &commands.len().to_string() + " commands"),
                     Err(e) => Err(e.to_string()),
                 }
             }
-            "heal" => {
-                if args.len() < 3 {
+            "replay" => {
+                if args.len() < 2 {
                     return Err("Not enough arguments".to_string());
                 }
-                let healing = args[1].parse::<u16>();
-                match healing {
-                    Ok(healing_amount) => {
-                        let entity_names =
-                            Vec::from_iter(args[2..].iter().map(|x| x.to_string().to_lowercase()));
-                        for entity in self.entities.iter_mut() {
-                            if entity_names.contains(&entity.name.to_lowercase()) {
-                                entity.damage_taken -= healing_amount;
-                            }
-                        }
-                        Ok("Healed entities".to_string())
+                if !flags.contains(&"--force")
+                    && (!self.entities.is_empty() || !self.log.is_empty())
+                {
+                    return Err("Replaying replaces this game, use --force to go ahead".to_string());
+                }
+                let contents = std::fs::read_to_string(args[1]).map_err(|e| e.to_string())?;
+                let mut replay = log::parse_replay(&contents)?;
+                replay.commands.retain(|(_, command, _)| {
+                    replayable(argumment_abreviations(
+                        command.split_whitespace().next().unwrap_or(""),
+                    ))
+                });
+                let length = replay.commands.len();
+                self.replay = Some(replay);
+                self.replay_to(length)
+            }
+            "replay_step" => {
+                let (position, length) = match self.get_replay() {
+                    Some(replay) => replay,
+                    None => return Err("No replay loaded".to_string()),
+                };
+                let step = match args.get(1) {
+                    Some(&"next") | None => (position + 1).min(length),
+                    Some(&"prev") => position.saturating_sub(1),
+                    Some(step) => step.parse::<usize>().map_err(|e| e.to_string())?,
+                };
+                self.replay_to(step)
+            }
+            "end_replay" => match self.replay.take() {
+                Some(_) => Ok("Continuing from this point".to_string()),
+                None => Err("No replay loaded".to_string()),
+            },
+            "stats" => {
+                let lifetime = flags.contains(&"--lifetime");
+                let targets = if args.len() > 1 {
+                    self.select_entities(&args[1..])
+                } else {
+                    (0..self.entities.len())
+                        .filter(|&i| self.entities[i].event.is_none())
+                        .collect()
+                };
+                if targets.is_empty() {
+                    return Err("No entities to show stats for".to_string());
+                }
+                let mut result = if lifetime {
+                    "Lifetime stats:".to_string()
+                } else {
+                    "Encounter stats:".to_string()
+                };
+                for i in targets {
+                    let entity = &self.entities[i];
+                    let stats = if lifetime {
+                        &entity.lifetime_stats
+                    } else {
+                        &entity.encounter_stats
+                    };
+                    result.push('\n');
+                    result.push_str(&entity.name);
+                    result.push_str(": ");
+                    result.push_str(&stats.to_string());
+                    if let Some(killer) = &entity.killed_by {
+                        result.push_str(", killed by ");
+                        result.push_str(killer);
                     }
-                    Err(e) => Err(e.to_string()),
                 }
+                Ok(result)
             }
-            "save" => {
+            "cr" | "xp" => {
                 if args.len() < 3 {
                     return Err("Not enough arguments".to_string());
                 }
-                save(
-                    self.clone(),
-                    args[2].to_string(),
-                    Team::from(args[1].to_string()),
-                )
+                let xp = if args[0] == "cr" {
+                    difficulty::cr_to_xp(args[1])
+                        .ok_or("Invalid challenge rating ".to_string() + args[1])?
+                } else {
+                    args[1].parse::<u32>().map_err(|e| e.to_string())?
+                };
+                let targets = self.targets(&args[2..])?;
+                for &i in targets.iter() {
+                    self.entities[i].xp = Some(xp);
+                }
+                Ok("Set XP value ".to_string()
+                    + &xp.to_string()
+                    + " for "
+                    + &self.target_names(&targets))
             }
-            "load" => {
+            "level" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let level = args[1].parse::<i16>().map_err(|e| e.to_string())?;
+                let targets = self.targets(&args[2..])?;
+                for &i in targets.iter() {
+                    self.entities[i].level = Some(level);
+                }
+                Ok("Set level for ".to_string() + &self.target_names(&targets))
+            }
+            "rules" => {
+                if let Some(rules) = args.get(1) {
+                    self.rules = difficulty::RuleSet::try_from(rules.to_string())?;
+                }
+                Ok("Using ".to_string() + &self.rules.to_string() + " rules")
+            }
+            "difficulty" => self.get_difficulty().ok_or(
+                "Needs party members with a level and opponents with XP or a level".to_string(),
+            ),
+            "end_combat" => Ok(self.end_combat()),
+            "encounters" => {
+                let names = encounter::list_encounters();
+                if names.is_empty() {
+                    return Ok("No encounters in ".to_string() + encounter::ENCOUNTER_DIRECTORY);
+                }
+                let mut result = "Encounters:".to_string();
+                for name in names {
+                    result.push('\n');
+                    match encounter::load_encounter(&name) {
+                        Ok(encounter) => result.push_str(&encounter.summary()),
+                        Err(e) => result.push_str(&e),
+                    }
+                }
+                Ok(result)
+            }
+            "start" => {
                 if args.len() < 2 {
                     return Err("Not enough arguments".to_string());
                 }
-                match load(args[1].to_string()) {
-                    Ok(entities) => {
-                        for entity in entities {
-                            self.entities.push(entity);
+                check_file_name(args[1])?;
+                let contents = self
+                    .read_file(&encounter::path(args[1]))
+                    .map_err(|_| "No encounter named ".to_string() + args[1])?;
+                let encounter = encounter::parse_encounter(args[1], &contents)?;
+                // all or nothing, a bad line leaves the game as it was
+                let before = self.clone();
+                let mut result = "Started ".to_string() + &encounter.name;
+                for command in encounter.commands() {
+                    match self.run_command(command.clone()) {
+                        Ok(message) => {
+                            result.push('\n');
+                            result.push_str(&message);
+                        }
+                        Err(e) => {
+                            *self = before;
+                            return Err(command + ": " + &e);
                         }
-                        Ok("Loaded".to_string())
                     }
-                    Err(e) => Err(e),
                 }
+                if let Some(map) = &encounter.map {
+                    result.push_str("\nMap: ");
+                    result.push_str(map);
+                }
+                for note in encounter.notes.iter() {
+                    result.push_str("\nNote: ");
+                    result.push_str(note);
+                }
+                self.encounter = Some(encounter);
+                Ok(result)
+            }
+            "strict" => {
+                self.strict = match args.get(1).map(|x| x.to_lowercase()).as_deref() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    None => !self.strict,
+                    _ => return Err("Expected on or off".to_string()),
+                };
+                if self.strict {
+                    Ok("Unmatched targets are now errors".to_string())
+                } else {
+                    Ok("Unmatched targets are now warnings".to_string())
+                }
+            }
+            "templates" => {
+                let (loaded, errors) = templates::load_templates();
+                let mut result = "Loaded ".to_string()
+                    + &loaded.len().to_string()
+                    + " stat blocks from "
+                    + templates::TEMPLATE_DIRECTORY;
+                for error in errors {
+                    result.push('\n');
+                    result.push_str(&error);
+                }
+                self.templates = Some(loaded);
+                Ok(result)
             }
             "clear" => {
+                self.encounter = None;
+                self.removed_in_combat.clear();
+                self.manual_order.clear();
+                self.turn = None;
+                self.round = 0;
+                // the campaign's party stays around for the next encounter
+                if self.campaign.is_some() && !flags.contains(&"--all") {
+                    self.entities
+                        .retain(|x| x.event.is_none() && x.team == "party");
+                    for entity in self.entities.iter_mut() {
+                        entity.initiative = None;
+                        entity.actions = ActionEconomy::default();
+                    }
+                    return Ok("Cleared entities, kept ".to_string() + &self.party_names());
+                }
                 self.entities.clear();
                 Ok("Cleared entities".to_string())
             }
+            "note" => {
+                // everything after the name and an optional --replace or --clear is the text,
+                // escaped by the ui to keep the command on one line
+                let rest = match command.trim_start().split_once(' ') {
+                    Some((_, rest)) => rest.trim_start(),
+                    None => "",
+                };
+                let (name, text) = rest.split_once(' ').unwrap_or((rest, ""));
+                if name.is_empty() {
+                    return Err("Not enough arguments".to_string());
+                }
+                let (replace, text) = match text.split_once(' ').unwrap_or((text, "")) {
+                    ("--replace" | "--clear", text) => (true, text),
+                    _ => (false, text),
+                };
+                let text = unescape_notes(text);
+                let targets = self.targets(&[name])?;
+                for &i in targets.iter() {
+                    let entity = &mut self.entities[i];
+                    if replace {
+                        entity.notes.clear();
+                    }
+                    if !text.is_empty() {
+                        if !entity.notes.is_empty() {
+                            entity.notes.push('\n');
+                        }
+                        entity.notes.push_str(&text);
+                    }
+                }
+                Ok("Updated notes for ".to_string() + &self.target_names(&targets))
+            }
+            "tag" | "untag" => {
+                if args.len() < 3 {
+                    return Err("Not enough arguments".to_string());
+                }
+                let tags = Vec::from_iter(args[2..].iter().map(|x| x.replace([',', '|'], "")));
+                let targets = self.targets(&args[1..2])?;
+                for &i in targets.iter() {
+                    let entity = &mut self.entities[i];
+                    for tag in tags.iter().filter(|x| !x.is_empty()) {
+                        let has = entity.tags.iter().any(|x| x.eq_ignore_ascii_case(tag));
+                        if args[0] == "tag" && !has {
+                            entity.tags.push(tag.clone());
+                        } else if args[0] == "untag" {
+                            entity.tags.retain(|x| !x.eq_ignore_ascii_case(tag));
+                        }
+                    }
+                }
+                Ok("Updated tags for ".to_string() + &self.target_names(&targets))
+            }
+            "campaign" => {
+                let Some(&name) = args.get(1) else {
+                    return match &self.campaign {
+                        Some(campaign) => {
+                            Ok("Campaign ".to_string() + campaign + ": " + &self.party_names())
+                        }
+                        None => Err("No campaign open".to_string()),
+                    };
+                };
+                check_file_name(name)?;
+                match self.load_campaign(name)? {
+                    Some(campaign) => {
+                        // the saved characters replace any copies already in the game
+                        self.entities
+                            .retain(|x| !campaign.characters.iter().any(|c| c.name == x.name));
+                        self.entities.extend(campaign.characters);
+                        self.clock = self.clock.max(campaign.clock);
+                        self.campaign = Some(name.to_string());
+                        Ok("Opened campaign ".to_string() + name + " with " + &self.party_names())
+                    }
+                    None => {
+                        if !self.replaying {
+                            save_campaign(self, name)?;
+                        }
+                        self.campaign = Some(name.to_string());
+                        Ok("Started campaign ".to_string() + name + " with " + &self.party_names())
+                    }
+                }
+            }
             "help" => {
                 if args.len() == 2 {
                     match args[1] {
                         "add_entity" => {
-                            return Ok("add_entity <name> <team>".to_string());
+                            return Ok(
+                                "add_entity <name> <faction> [x<copies>] [hit dice] [stat block] [--roll] [--letters] [--hidden]".to_string()
+                            );
+                        }
+                        "add_event" => {
+                            return Ok("add_event <name> <initiative> <reminder>".to_string());
                         }
                         "remove_entity" => {
-                            return Ok("remove_entity <name>".to_string());
+                            return Ok("remove_entity <names[]>".to_string());
                         }
                         "add_effect" => {
-                            return Ok("add_effect <effect> <length> <unit> <names[]>".to_string());
+                            return Ok("add_effect <effect> <length> <unit> <names[]> [--secret]"
+                                .to_string());
                         }
                         "remove_effect" => {
                             return Ok("remove_effect <effect> <names[]>".to_string());
                         }
                         "damage" => {
-                            return Ok("damage <amount> <names[]>".to_string());
+                            return Ok(
+                                "damage <amount> <names[]> [by <source>] [--crit]".to_string()
+                            );
                         }
                         "heal" => {
-                            return Ok("heal <amount> <names[]>".to_string());
+                            return Ok("heal <amount> <names[]> [by <source>]".to_string());
+                        }
+                        "rest" => {
+                            return Ok("rest <short | long> <names[] or a faction> [hit dice to spend, short rests only]".to_string());
+                        }
+                        "hit_dice" => {
+                            return Ok("hit_dice <count> <die, e.g. d8+2> <names[]>".to_string());
+                        }
+                        "exhaustion" => {
+                            return Ok("exhaustion <level> <names[]>".to_string());
+                        }
+                        "resource" => {
+                            return Ok(
+                                "resource <name> <max> <turn | round | short | long> <names[]>"
+                                    .to_string(),
+                            );
+                        }
+                        "use" => {
+                            return Ok("use <resource> [amount] <names[]>".to_string());
+                        }
+                        "restore" => {
+                            return Ok("restore <resource> [amount] <names[]>".to_string());
+                        }
+                        "hide" => {
+                            return Ok("hide <names[]>".to_string());
+                        }
+                        "reveal" => {
+                            return Ok("reveal <names[]>".to_string());
+                        }
+                        "max_hp" => {
+                            return Ok("max_hp <value> <names[]>".to_string());
+                        }
+                        "initiative" => {
+                            return Ok("initiative <value | roll> <names[]>".to_string());
+                        }
+                        "next" => {
+                            return Ok("next".to_string());
+                        }
+                        "move_turn" => {
+                            return Ok("move_turn <name> <position>".to_string());
+                        }
+                        "delay" => {
+                            return Ok("delay <name> <after name>".to_string());
+                        }
+                        "spend" => {
+                            return Ok("spend <action | bonus | reaction | movement> <names[]>"
+                                .to_string());
+                        }
+                        "regain" => {
+                            return Ok("regain <action | bonus | reaction | movement> <names[]>"
+                                .to_string());
+                        }
+                        "faction" => {
+                            return Ok(
+                                "faction <name> [color] [priority] [display name]".to_string()
+                            );
+                        }
+                        "factions" => {
+                            return Ok("factions".to_string());
+                        }
+                        "serve" => {
+                            return Ok("serve [port] [--lan]".to_string());
+                        }
+                        "stop_server" => {
+                            return Ok("stop_server".to_string());
+                        }
+                        "api" => {
+                            return Ok("api [port] [token] [--lan]".to_string());
+                        }
+                        "stop_api" => {
+                            return Ok("stop_api".to_string());
+                        }
+                        "export_log" => {
+                            return Ok("export_log <filename, .md or .html>".to_string());
+                        }
+                        "save_log" => {
+                            return Ok("save_log <filename>".to_string());
+                        }
+                        "replay" => {
+                            return Ok("replay <filename> [--force]".to_string());
+                        }
+                        "replay_step" => {
+                            return Ok("replay_step <step | next | prev>".to_string());
+                        }
+                        "end_replay" => {
+                            return Ok("end_replay".to_string());
+                        }
+                        "stats" => {
+                            return Ok("stats [names[]] [--lifetime]".to_string());
+                        }
+                        "cr" => {
+                            return Ok("cr <challenge rating> <names[]>".to_string());
+                        }
+                        "xp" => {
+                            return Ok("xp <value> <names[]>".to_string());
+                        }
+                        "level" => {
+                            return Ok("level <level> <names[]>".to_string());
+                        }
+                        "rules" => {
+                            return Ok("rules [5e | pf2e]".to_string());
+                        }
+                        "difficulty" => {
+                            return Ok("difficulty".to_string());
+                        }
+                        "end_combat" => {
+                            return Ok("end_combat".to_string());
+                        }
+                        "encounters" => {
+                            return Ok("encounters".to_string());
+                        }
+                        "start" => {
+                            return Ok("start <encounter>".to_string());
+                        }
+                        "strict" => {
+                            return Ok("strict [on | off]".to_string());
+                        }
+                        "templates" => {
+                            return Ok("templates".to_string());
+                        }
+                        "selectors" => {
+                            return Ok("names[] can be names, Goblin* wildcards or @all, @turn, @bloodied, @down, @hidden, @has:<effect>, @tag:<tag>, @note:<text>, @<faction>".to_string());
                         }
                         "clear" => {
-                            return Ok("clear".to_string());
+                            return Ok("clear [--all]".to_string());
+                        }
+                        "campaign" => {
+                            return Ok("campaign [name]".to_string());
+                        }
+                        "note" => {
+                            return Ok("note <name> [--replace | --clear] <text>".to_string());
+                        }
+                        "tag" => {
+                            return Ok("tag <name> <tags[]>".to_string());
+                        }
+                        "untag" => {
+                            return Ok("untag <name> <tags[]>".to_string());
+                        }
+                        "tabs" => {
+                            return Ok("tabs".to_string());
+                        }
+                        "new_tab" => {
+                            return Ok("new_tab [name]".to_string());
+                        }
+                        "tab" => {
+                            return Ok("tab <name | number>".to_string());
+                        }
+                        "rename_tab" => {
+                            return Ok("rename_tab <name>".to_string());
+                        }
+                        "close_tab" => {
+                            return Ok("close_tab [name | number]".to_string());
+                        }
+                        "move_to_tab" => {
+                            return Ok("move_to_tab <tab> <names[]>".to_string());
+                        }
+                        "save_tabs" => {
+                            return Ok("save_tabs <filename>".to_string());
+                        }
+                        "load_tabs" => {
+                            return Ok("load_tabs <filename>".to_string());
                         }
                         "save" => {
-                            return Ok("save <party | enemy | all> <filename>".to_string());
+                            return Ok("save <faction | all> <filename>".to_string());
                         }
                         "load" => {
                             return Ok("load <filename>".to_string());
                         }
                         _ => {
-                            return Ok("Valid Commands: add_entity, remove_entity, add_effect, remove_effect, damage, heal, clear, save, load".to_string());
+                            return Ok("Valid Commands: add_entity, add_event, remove_entity, add_effect, remove_effect, damage, heal, rest, hit_dice, exhaustion, resource, use, restore, hide, reveal, max_hp, initiative, next, move_turn, delay, spend, regain, faction, factions, serve, stop_server, api, stop_api, export_log, save_log, replay, replay_step, end_replay, stats, cr, xp, level, rules, difficulty, end_combat, encounters, start, strict, templates, note, tag, untag, campaign, tabs, new_tab, tab, rename_tab, close_tab, move_to_tab, save_tabs, load_tabs, clear, save, load".to_string());
                         }
                     }
                 }
-                Ok("Valid Commands: add_entity, remove_entity, add_effect, remove_effect, damage, heal, clear, save, load. Use help <command> for more info".to_string())
+                Ok("Valid Commands: add_entity, add_event, remove_entity, add_effect, remove_effect, damage, heal, rest, hit_dice, exhaustion, resource, use, restore, hide, reveal, max_hp, initiative, next, move_turn, delay, spend, regain, faction, factions, serve, stop_server, api, stop_api, export_log, save_log, replay, replay_step, end_replay, stats, cr, xp, level, rules, difficulty, end_combat, encounters, start, strict, templates, note, tag, untag, campaign, tabs, new_tab, tab, rename_tab, close_tab, move_to_tab, save_tabs, load_tabs, clear, save, load. Use help <command> for more info".to_string())
             }
             _ => Err("Unrecognized command use help to list commands".to_string()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(commands: &[&str]) -> Game {
+        let mut game = Game::new();
+        for command in commands {
+            game.process_command(command.to_string()).unwrap();
+        }
+        game
+    }
+
+    fn damage_taken(game: &Game, name: &str) -> u16 {
+        let entity = game.entities.iter().find(|x| x.name == name);
+        entity.unwrap().damage_taken
+    }
+
+    #[test]
+    fn use_changes_nothing_unless_every_target_has_enough() {
+        let mut game = game(&[
+            "add_entity Aria party",
+            "add_entity Bram party",
+            "resource Ki 2 short Aria Bram",
+            "use Ki Bram",
+        ]);
+        assert!(game
+            .process_command("use Ki 2 Aria Bram".to_string())
+            .is_err());
+        assert_eq!(game.entities[0].resources[0].current, 2);
+        assert_eq!(game.entities[1].resources[0].current, 1);
+        game.process_command("restore Ki 65535 Bram".to_string())
+            .unwrap();
+        assert_eq!(game.entities[1].resources[0].current, 2);
+    }
+
+    #[test]
+    fn reset_cadences_must_be_known() {
+        assert_eq!(
+            ResetCadence::try_from("S".to_string()),
+            Ok(ResetCadence::ShortRest)
+        );
+        assert!(ResetCadence::try_from("daily".to_string()).is_err());
+        let mut game = game(&["add_entity Aria party"]);
+        assert!(game
+            .process_command("resource Ki 2 daily Aria".to_string())
+            .is_err());
+    }
+
+    #[test]
+    fn factions_sharing_a_priority_stay_grouped() {
+        let mut game = game(&[
+            "faction cult red 5",
+            "faction bandits blue 5",
+            "add_entity One cult",
+            "add_entity Two bandits",
+            "add_entity Three cult",
+        ]);
+        let names = Vec::from_iter(game.get_entities().iter().map(|x| x.name().to_string()));
+        assert_eq!(names, ["Two", "One", "Three"]);
+    }
+
+    #[test]
+    fn faction_ids_cant_shadow_existing_names() {
+        let mut game = Game::new();
+        for id in ["p", "Allies", "e"] {
+            assert!(game.process_command("faction ".to_string() + id).is_err());
+        }
+        assert!(game
+            .process_command("faction party blue".to_string())
+            .is_ok());
+    }
+
+    #[test]
+    fn health_descriptor_handles_large_hit_points() {
+        let mut entity = Entity::new("Tarrasque".to_string(), UNKNOWN_FACTION.to_string());
+        entity.max_hp = Some(60000);
+        entity.damage_taken = 50000;
+        assert_eq!(entity.health_descriptor(), "Critical");
+        entity.damage_taken = 30000;
+        assert_eq!(entity.health_descriptor(), "Bloodied");
+    }
+
+    #[test]
+    fn unknown_flags_are_rejected() {
+        let mut game = Game::new();
+        assert!(game
+            .process_command("add_entity Aria party --hiden".to_string())
+            .is_err());
+        assert!(game.get_entities().is_empty());
+        assert!(game
+            .process_command("add_entity Aria party --hidden".to_string())
+            .is_ok());
+    }
+
+    #[test]
+    fn remote_commands_are_limited_to_the_fight() {
+        assert!(check_remote_command("d 5 Goblin").is_ok());
+        assert!(check_remote_command("next").is_ok());
+        for command in [
+            "api 9000",
+            "save ../x",
+            "export_log x.md",
+            "replay x",
+            "serve",
+        ] {
+            assert!(check_remote_command(command).is_err(), "{}", command);
+        }
+    }
+
+    #[test]
+    fn api_tokens_stay_out_of_the_log() {
+        assert_eq!(redact("api 0 secret --lan"), "api 0 <token> --lan");
+        assert_eq!(redact("damage 5 Goblin"), "damage 5 Goblin");
+        let mut game = Game::new();
+        game.process_command("api 0 secret".to_string()).unwrap();
+        game.process_command("stop_api".to_string()).unwrap();
+        let entry = &game.get_log()[0];
+        assert!(!entry.command.contains("secret"));
+        assert!(!entry.outcome_text().contains("secret"));
+    }
+
+    #[test]
+    fn replays_rebuild_the_same_game() {
+        let path = std::env::temp_dir().join("status_tracker_replay_test.txt");
+        let path = path.to_string_lossy().to_string();
+        let mut game = game(&[
+            "add_entity Aria party",
+            "add_entity Goblin enemy",
+            "initiative 15 Aria",
+            "initiative 10 Goblin",
+            "next",
+            "damage 7 Goblin",
+            "add_effect Prone 1 turns Goblin",
+            "factions",
+        ]);
+        game.process_command("save_log ".to_string() + &path)
+            .unwrap();
+        let mut replayed = Game::new();
+        replayed
+            .process_command("replay ".to_string() + &path)
+            .unwrap();
+        assert_eq!(replayed.get_replay(), Some((7, 7)));
+        assert_eq!(replayed.get_entities_list(), game.get_entities_list());
+        assert!(replayed
+            .process_command("replay ".to_string() + &path)
+            .is_err());
+        assert!(replayed
+            .process_command("replay ".to_string() + &path + " --force")
+            .is_ok());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn replays_keep_the_files_commands_read() {
+        let dir = std::env::temp_dir();
+        let path = dir.join("status_tracker_file_replay_test.txt");
+        let path = path.to_string_lossy().to_string();
+        let resaved = dir.join("status_tracker_file_replay_test_2.txt");
+        let resaved = resaved.to_string_lossy().to_string();
+        std::fs::write(
+            &path,
+            "seed|7\n\
+             100|load heroes\n\
+             file|2\n\
+             Aria|party|15\n\
+             Bram|party|12\n\
+             101|start ambush\n\
+             file|2\n\
+             monster|Goblin|2\n\
+             event|Collapse|20|the ceiling gives way\n\
+             102|next\n\
+             103|damage 4 Goblin_1\n",
+        )
+        .unwrap();
+        let mut replayed = Game::new();
+        assert_eq!(
+            replayed.process_command("replay ".to_string() + &path),
+            Ok("Replayed 4 of 4 commands".to_string())
+        );
+        assert_eq!(replayed.entities.len(), 5);
+        assert_eq!(damage_taken(&replayed, "Goblin_1"), 4);
+        replayed
+            .process_command("save_log ".to_string() + &resaved)
+            .unwrap();
+        let mut again = Game::new();
+        again
+            .process_command("replay ".to_string() + &resaved)
+            .unwrap();
+        assert_eq!(again.get_entities_list(), replayed.get_entities_list());
+
+        // a step that doesn't work any more stops the replay before it
+        std::fs::write(
+            &path,
+            "seed|7\n100|add_entity Aria party\n101|damage 4 Bram\n",
+        )
+        .unwrap();
+        let result = again.process_command("replay ".to_string() + &path + " --force");
+        assert!(result
+            .unwrap_err()
+            .starts_with("Replayed 1 of 2 commands, command 2 failed"));
+        assert_eq!(again.get_replay(), Some((1, 2)));
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&resaved);
+    }
+
+    fn uptime(game: &Game, name: &str) -> Vec<(String, u32)> {
+        let entity = game.entities.iter().find(|x| x.name == name);
+        entity.unwrap().encounter_stats.effect_uptime.clone()
+    }
+
+    #[test]
+    fn effect_uptime_is_counted_when_effects_end() {
+        let mut game = game(&[
+            "add_entity Aria party",
+            "initiative 10 Aria",
+            "next",
+            "add_effect Blessed 2 turns Aria",
+            "add_effect Prone 10 turns Aria",
+            "add_effect Cursed 0 unknown Aria",
+            "next",
+            "next",
+            "remove_effect Prone Aria",
+            "remove_effect Cursed Aria",
+        ]);
+        assert_eq!(
+            uptime(&game, "Aria"),
+            [("Blessed".to_string(), 12), ("Prone".to_string(), 12)]
+        );
+        game.process_command("add_effect Haste 1 minutes Aria".to_string())
+            .unwrap();
+        game.process_command("remove_effect Haste Aria".to_string())
+            .unwrap();
+        assert_eq!(uptime(&game, "Aria")[2], ("Haste".to_string(), 6));
+    }
+
+    #[test]
+    fn effect_names_cant_break_saves() {
+        let mut game = game(&["add_entity Aria party"]);
+        for name in ["a=b", "a,b", "a|b"] {
+            let command = "add_effect ".to_string() + name + " 1 turns Aria";
+            assert!(game.process_command(command).is_err());
+        }
+    }
+
+    #[test]
+    fn factions_are_only_selected_with_an_at() {
+        let mut game = game(&["add_entity Aria party", "add_entity Goblin enemy"]);
+        assert!(game.process_command("damage 5 e".to_string()).is_err());
+        assert!(game.process_command("damage 5 enemy".to_string()).is_err());
+        game.process_command("damage 5 @enemy".to_string()).unwrap();
+        assert_eq!(damage_taken(&game, "Goblin"), 5);
+        assert_eq!(damage_taken(&game, "Aria"), 0);
+    }
+
+    #[test]
+    fn copies_are_capped() {
+        let mut game = Game::new();
+        assert!(game
+            .process_command("add_entity Rat enemy x1000".to_string())
+            .is_err());
+        game.process_command("add_entity Rat enemy x100".to_string())
+            .unwrap();
+        assert_eq!(game.get_entities().len(), 100);
+    }
+
+    #[test]
+    fn bloodied_handles_large_hit_points() {
+        let mut game = game(&["add_entity Tarrasque enemy", "max_hp 60000 Tarrasque"]);
+        game.process_command("damage 20000 Tarrasque".to_string())
+            .unwrap();
+        assert!(game.select_entity("@bloodied").is_empty());
+        game.process_command("damage 20000 Tarrasque".to_string())
+            .unwrap();
+        assert_eq!(game.select_entity("@bloodied").len(), 1);
+    }
+
+    #[test]
+    fn removing_a_whole_faction_needs_an_at() {
+        let mut game = game(&["add_entity Aria party", "add_entity Bram party"]);
+        assert!(game.process_command("remove_entity p".to_string()).is_err());
+        assert!(game
+            .process_command("remove_entity party".to_string())
+            .is_err());
+        assert_eq!(game.get_entities().len(), 2);
+        game.process_command("remove_entity @party".to_string())
+            .unwrap();
+        assert!(game.get_entities().is_empty());
+    }
+
+    #[test]
+    fn wildcards_match_names() {
+        assert!(name_matches("goblin*", "Goblin_3"));
+        assert!(name_matches("*_3", "Goblin_3"));
+        assert!(name_matches("g*n_*", "Goblin_3"));
+        assert!(name_matches("GOBLIN", "goblin"));
+        assert!(!name_matches("goblin", "Goblin_3"));
+        assert!(!name_matches("orc*", "Goblin_3"));
+        assert!(!name_matches("*_3", "Goblin_30"));
+    }
+
+    #[test]
+    fn selectors_pick_the_right_entities() {
+        let mut game = game(&[
+            "add_entity Goblin enemy x3",
+            "add_entity Aria party",
+            "hide Goblin_2",
+        ]);
+        let names = |game: &Game, selector: &str| {
+            Vec::from_iter(
+                game.select_entities(&[selector])
+                    .iter()
+                    .map(|&i| game.entities[i].name.clone()),
+            )
+        };
+        assert_eq!(names(&game, "Goblin*").len(), 3);
+        assert_eq!(names(&game, "@hidden"), ["Goblin_2"]);
+        assert_eq!(names(&game, "@party"), ["Aria"]);
+        assert_eq!(names(&game, "@all").len(), 4);
+        game.process_command("add_effect Prone 1 turns Goblin_1".to_string())
+            .unwrap();
+        assert_eq!(names(&game, "@has:prone"), ["Goblin_1"]);
+    }
+
+    #[test]
+    fn edit_distance_counts_single_character_edits() {
+        assert_eq!(edit_distance("goblin", "goblin"), 0);
+        assert_eq!(edit_distance("Goblin", "gobiln"), 2);
+        assert_eq!(edit_distance("goblin", "goblins"), 1);
+        assert_eq!(edit_distance("", "orc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn unmatched_targets_get_suggestions() {
+        let mut game = game(&["add_entity Goblin enemy", "add_entity Aria party"]);
+        let error = game
+            .process_command("damage 5 Gobln".to_string())
+            .unwrap_err();
+        assert!(error.contains("did you mean Goblin?"), "{}", error);
+        let result = game
+            .process_command("damage 5 Goblin Nobody".to_string())
+            .unwrap();
+        assert!(
+            result.contains("Warning: No match for Nobody"),
+            "{}",
+            result
+        );
+        game.process_command("strict on".to_string()).unwrap();
+        assert!(game
+            .process_command("damage 5 Goblin Nobody".to_string())
+            .is_err());
+        assert_eq!(damage_taken(&game, "Goblin"), 5);
+        game.process_command("tag Goblin elite".to_string())
+            .unwrap();
+        for (typed, suggestion) in [("@tg:elite", "@tag:elite"), ("@nte:boss", "@note:boss")] {
+            let error = game
+                .process_command("damage 1 ".to_string() + typed)
+                .unwrap_err();
+            assert!(
+                error.contains(&("did you mean ".to_string() + suggestion)),
+                "{}",
+                error
+            );
+        }
+        let error = game
+            .process_command("damage 1 @has:prone".to_string())
+            .unwrap_err();
+        assert!(!error.contains("did you mean"), "{}", error);
+    }
+
+    #[test]
+    fn file_names_stay_in_their_folder() {
+        assert!(check_file_name("goblin_ambush").is_ok());
+        for name in ["", "../secrets", "a/b", "a\\b", ".."] {
+            assert!(check_file_name(name).is_err(), "{}", name);
+        }
+        let mut game = Game::new();
+        assert!(game
+            .process_command("start ../../etc/passwd".to_string())
+            .is_err());
+        assert!(game.process_command("save all ../x".to_string()).is_err());
+        assert!(game.process_command("load ../x".to_string()).is_err());
+    }
+
+    #[test]
+    fn campaigns_keep_the_party_between_sessions() {
+        let game = game(&[
+            "add_entity Aria party",
+            "add_entity Goblin enemy",
+            "max_hp 30 Aria",
+            "damage 12 Aria",
+            "level 3 Aria",
+            "resource Ki 3 short Aria",
+            "use Ki Aria",
+            "add_effect Blessed 1 hours Aria",
+            "note Aria owes the innkeeper",
+        ]);
+        let saved = "clock|10\n\
+                     character|Aria|0|||0|0||0||0\n\
+                     character|Bram|0|||0|0||0||0\n";
+        let saved = parse_campaign(saved).unwrap();
+        let campaign = parse_campaign(&campaign_text(&game, saved)).unwrap();
+        // party members from other tabs are kept
+        assert_eq!(campaign.characters.len(), 2);
+        assert_eq!(campaign.characters[1].name, "Bram");
+        let aria = &campaign.characters[0];
+        let before = &game.entities[game.find_entity("Aria").unwrap()];
+        assert_eq!(aria.damage_taken, 12);
+        assert_eq!(aria.level, Some(3));
+        assert_eq!(aria.resources, before.resources);
+        assert_eq!(aria.status_effects, before.status_effects);
+        assert_eq!(aria.notes, "owes the innkeeper");
+        assert!(game
+            .clone()
+            .process_command("campaign ../x".to_string())
+            .is_err());
+    }
+
+    #[test]
+    fn notes_are_taken_as_typed() {
+        let mut game = game(&[
+            "add_entity Aria party",
+            "note Aria  swore an oath --never to  return",
+            "note Aria paid 5gp\\nowes 2gp",
+        ]);
+        let notes = |game: &Game| game.entities[0].notes.clone();
+        assert_eq!(
+            notes(&game),
+            " swore an oath --never to  return\npaid 5gp\nowes 2gp"
+        );
+        game.process_command("note Aria --replace C:\\\\maps\\\\new".to_string())
+            .unwrap();
+        assert_eq!(notes(&game), "C:\\maps\\new");
+        game.process_command("note Aria --clear".to_string())
+            .unwrap();
+        assert_eq!(notes(&game), "");
+    }
+
+    #[test]
+    fn notes_escaping_round_trips() {
+        for notes in [
+            "plain",
+            "two\nlines",
+            "a literal \\n",
+            "\\\\server\\share",
+            "ends with \\",
+        ] {
+            assert_eq!(unescape_notes(&escape_notes(notes)), notes);
+        }
+        let mut game = game(&["add_entity Aria party"]);
+        game.entities[0].notes = "a literal \\n\nand a | pipe".to_string();
+        let loaded = parse_save(&save_text(&game, &None, true)).unwrap();
+        let mut reloaded = Game::new();
+        reloaded.apply_save(loaded);
+        assert_eq!(reloaded.entities[0].notes, game.entities[0].notes);
+    }
+
+    #[test]
+    fn names_cant_break_saves() {
+        let mut game = game(&[
+            "add_entity Orders party",
+            "add_entity Event_Horizon enemy",
+            "add_entity Stat enemy x2",
+            "add_event Combatant 10 lair action",
+            "initiative 12 Orders",
+        ]);
+        for command in [
+            "add_entity order party",
+            "add_entity Notes party",
+            "add_entity Bad|Name party",
+            "add_entity tags enemy x2",
+            "add_event State 10 lair action",
+            "faction Effect",
+            "faction cult red 10 The|Cult",
+        ] {
+            assert!(
+                game.process_command(command.to_string()).is_err(),
+                "{}",
+                command
+            );
+        }
+        let mut reloaded = Game::new();
+        reloaded.load_save_text(&game.to_save_text()).unwrap();
+        let mut names = Vec::from_iter(reloaded.entities.iter().map(|x| x.name.as_str()));
+        names.sort();
+        assert_eq!(
+            names,
+            ["Combatant", "Event_Horizon", "Orders", "Stat_1", "Stat_2"]
+        );
+        let orders = reloaded.entities.iter().find(|x| x.name == "Orders");
+        assert_eq!(orders.unwrap().initiative, Some(12));
+    }
+
+    #[test]
+    fn game_saves_keep_the_settings() {
+        let mut game = game(&["add_entity Aria party", "rules pf2e", "strict on"]);
+        game.campaign = Some("west marches".to_string());
+        let mut reloaded = Game::new();
+        reloaded.load_save_text(&game.to_save_text()).unwrap();
+        assert_eq!(reloaded.rules, difficulty::RuleSet::Pathfinder2e);
+        assert!(reloaded.strict);
+        assert_eq!(reloaded.get_campaign(), Some("west marches"));
+        assert_eq!(reloaded.entities.len(), 1);
+        let mut old = Game::new();
+        old.load_save_text("Aria|party").unwrap();
+        assert_eq!(old.get_campaign(), None);
+    }
+
+    #[test]
+    fn names_cant_look_like_selectors() {
+        let mut game = Game::new();
+        for command in [
+            "add_entity @all party",
+            "add_entity Gob* enemy",
+            "add_event @turn 20 lair action",
+            "add_event Lair* 20 lair action",
+        ] {
+            assert!(
+                game.process_command(command.to_string()).is_err(),
+                "{}",
+                command
+            );
+        }
+        assert!(game
+            .process_command("add_entity Aria@Home party".to_string())
+            .is_ok());
+    }
+
+    #[test]
+    fn removing_whoever_has_the_turn_passes_it_on() {
+        let mut game = game(&[
+            "add_entity Ash party",
+            "add_entity Bree party",
+            "add_entity Cole party",
+            "initiative 20 Ash",
+            "initiative 15 Bree",
+            "initiative 10 Cole",
+            "next",
+            "next",
+        ]);
+        let result = game
+            .process_command("remove_entity Bree".to_string())
+            .unwrap();
+        assert!(result.ends_with("Round 1, Cole's turn"), "{}", result);
+        assert_eq!(game.turn.as_deref(), Some("Cole"));
+        let result = game.process_command("next".to_string()).unwrap();
+        assert!(result.contains("Round 2, Ash"), "{}", result);
+        game.process_command("remove_entity Ash Cole".to_string())
+            .unwrap();
+        assert_eq!(game.turn, None);
+    }
+
+    #[test]
+    fn resource_names_cant_break_saves() {
+        let mut game = game(&["add_entity Aria party"]);
+        for name in ["Ki|2", "Ki,Rage", "Ki=3"] {
+            let command = "resource ".to_string() + name + " 3 short Aria";
+            assert!(game.process_command(command).is_err());
+        }
+        assert!(game.entities[0].resources.is_empty());
+    }
+
+    #[test]
+    fn reminders_cant_break_saves() {
+        let mut game = Game::new();
+        assert!(game
+            .process_command("add_event Lair 20 the roof | collapses".to_string())
+            .is_err());
+        assert!(game
+            .process_command("add_event Lair 20 roll 1d6|2d6".to_string())
+            .is_err());
+        game.process_command("add_event Lair 20 roof collapses".to_string())
+            .unwrap();
+        assert_eq!(game.entities.len(), 1);
+    }
+
+    #[test]
+    fn huge_numbers_dont_overflow() {
+        let mut game = game(&[
+            "add_entity Aria party",
+            "damage 60000 Aria",
+            "damage 60000 Aria",
+            "add_effect Cursed 65535 hours Aria",
+        ]);
+        assert_eq!(damage_taken(&game, "Aria"), u16::MAX);
+        game.clock = u32::MAX - 10;
+        game.process_command("rest long Aria".to_string()).unwrap();
+        assert_eq!(game.clock, u32::MAX);
+    }
+
+    #[test]
+    fn rest_takes_a_bare_faction_name() {
+        let mut game = game(&[
+            "add_entity Aria party",
+            "add_entity Bram party",
+            "add_entity Goblin enemy",
+            "max_hp 20 Aria Bram Goblin",
+            "damage 5 Aria Bram Goblin",
+            "rest long party",
+        ]);
+        assert_eq!(damage_taken(&game, "Aria"), 0);
+        assert_eq!(damage_taken(&game, "Bram"), 0);
+        assert_eq!(damage_taken(&game, "Goblin"), 5);
+        game.process_command("rest long Goblin".to_string())
+            .unwrap();
+        assert_eq!(damage_taken(&game, "Goblin"), 0);
+    }
+
+    #[test]
+    fn opponents_removed_mid_fight_still_give_xp() {
+        let mut game = game(&[
+            "add_entity Aria party",
+            "add_entity Rat enemy",
+            "add_entity Goblin enemy",
+            "add_entity Orc enemy",
+            "add_entity Wolf enemy",
+            "xp 25 Rat",
+            "xp 50 Goblin",
+            "xp 100 Orc",
+            "xp 200 Wolf",
+            "max_hp 10 Orc",
+            "remove_entity Rat",
+            "initiative 10 Aria Goblin Orc Wolf",
+            "next",
+            "damage 10 Orc",
+            "remove_entity Goblin Orc",
+        ]);
+        let result = game.process_command("end_combat".to_string()).unwrap();
+        assert!(result.contains("150 XP from 2 defeated"), "{}", result);
+        assert_eq!(game.entities[0].experience, 150);
+        game.process_command("end_combat".to_string()).unwrap();
+        assert_eq!(game.entities[0].experience, 150);
+    }
+
+    #[test]
+    fn short_rest_spends_only_the_hit_dice_asked_for() {
+        let mut game = game(&[
+            "add_entity Aria party",
+            "hit_dice 3 d1 Aria",
+            "damage 5 Aria",
+        ]);
+        game.process_command("rest short Aria".to_string()).unwrap();
+        assert_eq!(damage_taken(&game, "Aria"), 5);
+        game.process_command("rest short Aria 2".to_string())
+            .unwrap();
+        assert_eq!(damage_taken(&game, "Aria"), 3);
+        assert_eq!(game.entities[0].hit_dice_remaining, 1);
+    }
+}